@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::dat::ParsedDat;
+
+// GameEntry 或 DAT 头信息的结构、解析或转写结果变化时需要递增，旧缓存会被整体丢弃
+const CACHE_VERSION: u32 = 12;
+
+// 用于判断 DAT 是否变化的文件指纹
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FileStamp {
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub size: u64,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            size: meta.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CachedDat {
+    pub stamp: FileStamp,
+    pub dat: ParsedDat,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct IndexCache {
+    version: u32,
+    // 以 DAT 文件路径为键
+    pub files: HashMap<String, CachedDat>,
+    pub platforms: Vec<String>,
+    pub regions: Vec<String>,
+    pub languages: Vec<String>,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("retro_game_search").join("index.bin"))
+}
+
+impl IndexCache {
+    pub fn new() -> Self {
+        Self {
+            version: CACHE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    // 读取失败或版本不一致时返回空缓存
+    pub fn load() -> Self {
+        let loaded = cache_path()
+            .and_then(|p| File::open(p).ok())
+            .and_then(|f| bincode::deserialize_from::<_, IndexCache>(BufReader::new(f)).ok());
+        match loaded {
+            Some(c) if c.version == CACHE_VERSION => c,
+            _ => Self::new(),
+        }
+    }
+
+    // 取出未变化的 DAT 的解析结果
+    pub fn take_fresh(&mut self, path: &Path, stamp: FileStamp) -> Option<ParsedDat> {
+        let key = path.display().to_string();
+        match self.files.get(&key) {
+            Some(c) if c.stamp == stamp => self.files.remove(&key).map(|c| c.dat),
+            _ => None,
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = cache_path().ok_or_else(|| anyhow!("无法获取缓存目录"))?;
+        let dir = path.parent().ok_or_else(|| anyhow!("无效的缓存路径"))?;
+        fs::create_dir_all(dir).context("无法创建缓存目录")?;
+        // 先写临时文件再替换，避免中途退出留下损坏的缓存
+        let tmp = path.with_extension("bin.tmp");
+        {
+            let file =
+                File::create(&tmp).with_context(|| format!("无法写入缓存: {}", tmp.display()))?;
+            bincode::serialize_into(BufWriter::new(file), self).context("序列化索引缓存失败")?;
+        }
+        fs::rename(&tmp, &path).with_context(|| format!("无法写入缓存: {}", path.display()))
+    }
+}
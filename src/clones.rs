@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::xml::GameEntry;
+
+// 父版本/克隆关系：按 cloneof（No-Intro 由 archive@clone 换算成名称）在同一个 DAT 中查找父版本
+#[derive(Debug, Default)]
+pub struct CloneIndex {
+    // 每个条目所属的最上层父版本（自身是父版本或找不到父版本时为 None）
+    parent: Vec<Option<usize>>,
+    // 父版本 -> 克隆，按索引中的顺序
+    clones: HashMap<usize, Vec<usize>>,
+}
+
+// 结果列表中的一组：组首和同组其他匹配的版本
+#[derive(Debug)]
+pub struct ResultGroup {
+    pub head: usize,
+    pub members: Vec<usize>,
+}
+
+impl CloneIndex {
+    pub fn build(index: &[GameEntry]) -> Self {
+        let by_name: HashMap<(&str, Option<&str>, &str), usize> = index
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                let key = (
+                    g.file_path.as_str(),
+                    g.dat_member.as_deref(),
+                    g.name.as_str(),
+                );
+                (key, i)
+            })
+            .collect();
+        let direct: Vec<Option<usize>> = index
+            .iter()
+            .enumerate()
+            .map(|(i, g)| {
+                let parent = g.clone_of.as_deref()?;
+                let key = (g.file_path.as_str(), g.dat_member.as_deref(), parent);
+                by_name.get(&key).copied().filter(|&p| p != i)
+            })
+            .collect();
+
+        // 克隆的克隆归到最上层的父版本下；DAT 中有循环引用时不分组
+        let mut parent = vec![None; index.len()];
+        let mut clones: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, slot) in parent.iter_mut().enumerate() {
+            let mut root = i;
+            let mut seen = HashSet::new();
+            while let Some(p) = direct[root] {
+                if !seen.insert(p) {
+                    root = i;
+                    break;
+                }
+                root = p;
+            }
+            if root != i {
+                *slot = Some(root);
+                clones.entry(root).or_default().push(i);
+            }
+        }
+        Self { parent, clones }
+    }
+
+    // 条目所在组的父版本，没有父版本时为自身
+    pub fn root_of(&self, i: usize) -> usize {
+        self.parent.get(i).copied().flatten().unwrap_or(i)
+    }
+
+    // 同一组中除自身以外的所有版本，父版本排在最前
+    pub fn siblings(&self, i: usize) -> Vec<usize> {
+        let root = self.root_of(i);
+        std::iter::once(root)
+            .chain(self.clones.get(&root).into_iter().flatten().copied())
+            .filter(|&s| s != i)
+            .collect()
+    }
+
+    pub fn is_parent_of(&self, parent: usize, i: usize) -> bool {
+        parent != i && self.root_of(i) == parent
+    }
+
+    // 把结果按父版本分组，组的顺序取组内第一个结果的位置；
+    // 父版本也在结果中时作为组首，否则由第一个匹配的克隆代替
+    pub fn group(&self, results: &[usize]) -> Vec<ResultGroup> {
+        let mut groups: Vec<ResultGroup> = Vec::new();
+        let mut slots: HashMap<usize, usize> = HashMap::new();
+        for &i in results {
+            let root = self.root_of(i);
+            match slots.get(&root) {
+                Some(&g) => groups[g].members.push(i),
+                None => {
+                    slots.insert(root, groups.len());
+                    groups.push(ResultGroup {
+                        head: i,
+                        members: Vec::new(),
+                    });
+                }
+            }
+        }
+        for group in groups.iter_mut() {
+            let root = self.root_of(group.head);
+            if let Some(pos) = group.members.iter().position(|&m| m == root) {
+                group.members.remove(pos);
+                group.members.insert(0, group.head);
+                group.head = root;
+            }
+        }
+        groups
+    }
+}
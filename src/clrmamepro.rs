@@ -0,0 +1,379 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::dat::{DatHeader, DatSource};
+use crate::tags::NameTags;
+use crate::xml::{FieldSection, GameEntry, ParsePosition, RomEntry};
+
+// ClrMamePro 文本格式 DAT：
+//   clrmamepro ( name "..." )
+//   game ( name "..." year 1989 rom ( name "..." size 32768 crc 46DF91AD ) )
+// 顶层由若干 "关键字 ( ... )" 块组成，块内是键值对，值可以是单词、带引号的字符串或嵌套块
+
+#[derive(Debug, Clone)]
+enum Value {
+    Text(String),
+    Block(Vec<(String, Value)>),
+}
+
+// 顶层块及其在文件中的字节范围
+struct TopBlock {
+    keyword: String,
+    start: usize,
+    end: usize,
+    fields: Vec<(String, Value)>,
+}
+
+// 顶层中表示游戏条目的关键字
+fn is_game_keyword(keyword: &str) -> bool {
+    matches!(keyword, "game" | "machine" | "resource")
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+}
+
+// 按字节切分，非 UTF-8 的文件也能保证字节位置准确
+struct Tokenizer<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(src: &'a [u8]) -> Self {
+        Self { src, pos: 0 }
+    }
+
+    // 返回 (起始字节位置, 记号)，到达末尾时返回 None
+    fn next_token(&mut self) -> Result<Option<(usize, Token)>> {
+        let bytes = self.src;
+        while self.pos < bytes.len() && bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let Some(&b) = bytes.get(start) else {
+            return Ok(None);
+        };
+        let token = match b {
+            b'(' => {
+                self.pos += 1;
+                Token::Open
+            }
+            b')' => {
+                self.pos += 1;
+                Token::Close
+            }
+            // 字符串中的 \" 表示引号本身，其他反斜杠（如 ROM 路径中的目录分隔符）保持原样
+            b'"' => {
+                let mut end = start + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    end += if bytes[end] == b'\\' && bytes.get(end + 1) == Some(&b'"') {
+                        2
+                    } else {
+                        1
+                    };
+                }
+                if end >= bytes.len() {
+                    return Err(anyhow!("字符串缺少结束引号")).context(ParsePosition(start));
+                }
+                self.pos = end + 1;
+                Token::Word(lossy(&bytes[start + 1..end]).replace("\\\"", "\""))
+            }
+            _ => {
+                let end = bytes[start..]
+                    .iter()
+                    .position(|&c| c.is_ascii_whitespace() || c == b'(' || c == b')')
+                    .map(|i| start + i)
+                    .unwrap_or(bytes.len());
+                self.pos = end;
+                Token::Word(lossy(&bytes[start..end]))
+            }
+        };
+        Ok(Some((start, token)))
+    }
+
+    fn expect_open(&mut self) -> Result<()> {
+        match self.next_token()? {
+            Some((_, Token::Open)) => Ok(()),
+            Some((pos, _)) => Err(anyhow!("此处应为 \"(\"")).context(ParsePosition(pos)),
+            None => Err(anyhow!("文件意外结束")).context(ParsePosition(self.pos)),
+        }
+    }
+
+    // 读取块内容直到匹配的 ")"，返回键值对和 ")" 之后的位置
+    fn parse_block(&mut self) -> Result<(Vec<(String, Value)>, usize)> {
+        let mut fields = Vec::new();
+        loop {
+            let key = match self.next_token()? {
+                Some((_, Token::Close)) => return Ok((fields, self.pos)),
+                Some((_, Token::Word(w))) => w,
+                Some((pos, Token::Open)) => {
+                    return Err(anyhow!("此处应为字段名")).context(ParsePosition(pos))
+                }
+                None => return Err(anyhow!("块缺少结束的 \")\"")).context(ParsePosition(self.pos)),
+            };
+            let value = match self.next_token()? {
+                Some((_, Token::Open)) => Value::Block(self.parse_block()?.0),
+                Some((_, Token::Word(w))) => Value::Text(w),
+                Some((pos, Token::Close)) => {
+                    return Err(anyhow!("字段 \"{}\" 缺少值", key)).context(ParsePosition(pos))
+                }
+                None => return Err(anyhow!("文件意外结束")).context(ParsePosition(self.pos)),
+            };
+            fields.push((key, value));
+        }
+    }
+}
+
+fn parse_top_blocks(src: &[u8]) -> Result<Vec<TopBlock>> {
+    let mut tokenizer = Tokenizer::new(src);
+    let mut blocks = Vec::new();
+    while let Some((start, token)) = tokenizer.next_token()? {
+        let Token::Word(keyword) = token else {
+            return Err(anyhow!("此处应为块关键字")).context(ParsePosition(start));
+        };
+        tokenizer.expect_open()?;
+        let (fields, end) = tokenizer.parse_block()?;
+        blocks.push(TopBlock {
+            keyword,
+            start,
+            end,
+            fields,
+        });
+    }
+    Ok(blocks)
+}
+
+fn text_field(fields: &[(String, Value)], key: &str) -> Option<String> {
+    fields.iter().find_map(|(k, v)| match v {
+        Value::Text(t) if k == key => Some(t.clone()),
+        _ => None,
+    })
+}
+
+fn rom_from_fields(fields: &[(String, Value)]) -> RomEntry {
+    let mut rom = RomEntry::default();
+    for (k, v) in fields {
+        let Value::Text(v) = v else {
+            continue;
+        };
+        match k.as_str() {
+            "name" => rom.name = v.clone(),
+            "size" => rom.size = v.parse().ok(),
+            "crc" => rom.crc = Some(v.to_lowercase()),
+            "md5" => rom.md5 = Some(v.to_lowercase()),
+            "sha1" => rom.sha1 = Some(v.to_lowercase()),
+            _ => {}
+        }
+    }
+    rom
+}
+
+// 把块内的所有字段按块分组；ROM 和磁盘已单独列出，不再收集。
+// 子块的分组名为 prefix + 字段名，条目本身的 prefix 为空
+fn collect_sections(
+    path: &str,
+    prefix: &str,
+    fields: &[(String, Value)],
+    out: &mut Vec<FieldSection>,
+) {
+    let index = out.len();
+    out.push(FieldSection {
+        name: path.to_string(),
+        values: Vec::new(),
+    });
+    for (k, v) in fields {
+        match v {
+            Value::Text(t) => out[index].values.push((k.clone(), t.clone())),
+            Value::Block(_) if k == "rom" || k == "disk" => {}
+            Value::Block(sub) => {
+                let sub_path = format!("{}{}", prefix, k);
+                collect_sections(&sub_path, &format!("{}/", sub_path), sub, out);
+            }
+        }
+    }
+    if out[index].values.is_empty() {
+        out.remove(index);
+    }
+}
+
+pub fn parse_games(src: &[u8], source: &DatSource) -> Result<(DatHeader, Vec<GameEntry>)> {
+    let blocks = parse_top_blocks(src).with_context(|| format!("解析失败: {}", source))?;
+    let mut header = DatHeader::default();
+    if let Some(block) = blocks.iter().find(|b| b.keyword == "clrmamepro") {
+        for (k, v) in &block.fields {
+            if let Value::Text(v) = v {
+                header.set(k, v.clone());
+            }
+        }
+    }
+    let platform = source.platform(&header);
+
+    let mut results = Vec::new();
+    for block in blocks.iter().filter(|b| is_game_keyword(&b.keyword)) {
+        let Some(name) = text_field(&block.fields, "name") else {
+            continue;
+        };
+        let sub_blocks = |key: &str| -> Vec<RomEntry> {
+            block
+                .fields
+                .iter()
+                .filter_map(|(k, v)| match v {
+                    Value::Block(fields) if k == key => Some(rom_from_fields(fields)),
+                    _ => None,
+                })
+                .collect()
+        };
+        results.push(GameEntry {
+            platform: platform.clone(),
+            name,
+            archive_name: None,
+            region: text_field(&block.fields, "region"),
+            languages: None,
+            description: text_field(&block.fields, "description"),
+            year: text_field(&block.fields, "year"),
+            manufacturer: text_field(&block.fields, "manufacturer"),
+            clone_of: text_field(&block.fields, "cloneof"),
+            rom_of: text_field(&block.fields, "romof"),
+            driver_status: None,
+            tags: NameTags::default(),
+            romanized: None,
+            roms: sub_blocks("rom"),
+            disks: sub_blocks("disk"),
+            fields: {
+                let mut sections = Vec::new();
+                collect_sections(&block.keyword, "", &block.fields, &mut sections);
+                sections
+            },
+            library: String::new(),
+            file_path: source.path.display().to_string(),
+            dat_member: source.member.map(str::to_string),
+            source_span: block.start as u64..block.end as u64,
+        });
+    }
+    Ok((header, results))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const DAT: &str = r#"clrmamepro (
+	name "Nintendo - Game Boy"
+	description "Nintendo - Game Boy (20240101)"
+	version 20240101
+)
+
+game (
+	name "Tetris (World) (Rev 1)"
+	description "Tetris (World) (Rev 1)"
+	year 1989
+	rom ( name "Tetris (World) (Rev 1).gb" size 32768 crc 46DF91AD md5 084F1E457749CDEC86183189BD88CE69 sha1 74591CC9501AF93873F9A5D3EB12DA12C0723BBC )
+)
+
+game (
+	name "Tetris (Japan)"
+	cloneof "Tetris (World) (Rev 1)"
+	rom ( name "Disc\track01.bin" size 16 crc 00000001 )
+	rom ( name "The \"Quoted\" Name.gb" size 8 crc 00000002 )
+)
+
+resource (
+	name "neogeo"
+	rom ( name "sp-s2.sp1" size 131072 crc 9036d879 )
+)
+
+machine (
+	name "pacman"
+	manufacturer "Namco"
+	driver ( status good )
+)
+"#;
+
+    fn parse(src: &str) -> (DatHeader, Vec<GameEntry>) {
+        let source = DatSource {
+            path: Path::new("test.dat"),
+            member: None,
+        };
+        parse_games(src.as_bytes(), &source).unwrap()
+    }
+
+    #[test]
+    fn parses_header_games_and_roms() {
+        let (header, games) = parse(DAT);
+        assert_eq!(header.name.as_deref(), Some("Nintendo - Game Boy"));
+        assert_eq!(header.version.as_deref(), Some("20240101"));
+
+        let names: Vec<&str> = games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Tetris (World) (Rev 1)",
+                "Tetris (Japan)",
+                "neogeo",
+                "pacman"
+            ]
+        );
+        assert!(games.iter().all(|g| g.platform == "Nintendo - Game Boy"));
+
+        let parent = &games[0];
+        assert_eq!(parent.year.as_deref(), Some("1989"));
+        assert_eq!(parent.clone_of, None);
+        let rom = &parent.roms[0];
+        assert_eq!(rom.name, "Tetris (World) (Rev 1).gb");
+        assert_eq!(rom.size, Some(32768));
+        assert_eq!(rom.crc.as_deref(), Some("46df91ad"));
+        assert_eq!(rom.md5.as_deref(), Some("084f1e457749cdec86183189bd88ce69"));
+        assert_eq!(
+            rom.sha1.as_deref(),
+            Some("74591cc9501af93873f9a5d3eb12da12c0723bbc")
+        );
+
+        let clone = &games[1];
+        assert_eq!(clone.clone_of.as_deref(), Some("Tetris (World) (Rev 1)"));
+        let rom_names: Vec<&str> = clone.roms.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(rom_names, ["Disc\\track01.bin", "The \"Quoted\" Name.gb"]);
+
+        assert_eq!(games[2].roms[0].crc.as_deref(), Some("9036d879"));
+        // 嵌套块作为单独的字段分组
+        let pacman = &games[3];
+        assert_eq!(pacman.manufacturer.as_deref(), Some("Namco"));
+        assert!(pacman.fields.iter().any(
+            |s| s.name == "driver" && s.values == [("status".to_string(), "good".to_string())]
+        ));
+    }
+
+    #[test]
+    fn source_span_covers_the_whole_block() {
+        let (_, games) = parse(DAT);
+        for g in &games {
+            let block = &DAT[g.source_span.start as usize..g.source_span.end as usize];
+            assert!(block.ends_with(')'), "{}", block);
+            assert!(block.contains(&format!("name \"{}\"", g.name.replace('"', "\\\""))));
+        }
+        let span = &games[1].source_span;
+        let block = &DAT[span.start as usize..span.end as usize];
+        assert!(block.starts_with("game ("));
+        assert!(block.contains("The \\\"Quoted\\\" Name.gb"));
+    }
+
+    #[test]
+    fn reports_unterminated_blocks_and_strings() {
+        let source = DatSource {
+            path: Path::new("test.dat"),
+            member: None,
+        };
+        for src in ["game ( name \"a\" rom ( name \"b\" )", "game ( name \"a )"] {
+            let err = parse_games(src.as_bytes(), &source).unwrap_err();
+            assert!(err.downcast_ref::<ParsePosition>().is_some(), "{:#}", err);
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use zip::CompressionMethod;
+
+use crate::clrmamepro;
+use crate::normalize;
+use crate::tags::NameTags;
+use crate::verify::is_zip;
+use crate::xml::{self, infer_platform_from_filename, GameEntry};
+
+// DAT 文件格式，按内容判断（.dat 既可能是 XML，也可能是 ClrMamePro 文本格式）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatFormat {
+    Xml,
+    ClrMamePro,
+}
+
+// DAT 头信息：XML 的 <header> 或 ClrMamePro 的 clrmamepro ( ... ) 块
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct DatHeader {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub date: Option<String>,
+    pub author: Option<String>,
+    pub homepage: Option<String>,
+}
+
+impl DatHeader {
+    // 按字段名填写，忽略不关心的字段
+    pub fn set(&mut self, key: &str, value: String) {
+        let field = match key {
+            "name" => &mut self.name,
+            "description" => &mut self.description,
+            "version" => &mut self.version,
+            "date" => &mut self.date,
+            "author" => &mut self.author,
+            "homepage" | "url" => &mut self.homepage,
+            _ => return,
+        };
+        if field.is_none() && !value.is_empty() {
+            *field = Some(value);
+        }
+    }
+}
+
+// 一个已加载的 DAT（zip 中的每个成员各算一个），用于「DAT 文件」列表
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DatInfo {
+    pub file_path: String,
+    pub member: Option<String>,
+    // 所属 DAT 库名称，由加载器填写
+    pub library: String,
+    pub header: DatHeader,
+    pub game_count: usize,
+}
+
+// 一个 DAT 文件的解析结果
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ParsedDat {
+    pub infos: Vec<DatInfo>,
+    pub games: Vec<GameEntry>,
+}
+
+// 一个可解析的 DAT：普通文件、gzip 文件，或 zip 压缩包中的一个成员
+#[derive(Debug, Clone, Copy)]
+pub struct DatSource<'a> {
+    pub path: &'a Path,
+    pub member: Option<&'a str>,
+}
+
+impl DatSource<'_> {
+    // 平台名称优先取 DAT 头中的名称，否则由文件名推断
+    pub fn platform(&self, header: &DatHeader) -> String {
+        if let Some(name) = header.name.as_ref().filter(|n| !n.trim().is_empty()) {
+            return name.trim().to_string();
+        }
+        // zip 成员使用成员名，gzip 去掉 .gz 后缀
+        let name: PathBuf = match self.member {
+            Some(m) => PathBuf::from(m),
+            None if is_gzip(self.path) => self.path.with_extension(""),
+            None => self.path.to_path_buf(),
+        };
+        infer_platform_from_filename(&name).unwrap_or_else(|| "Unknown".to_string())
+    }
+}
+
+impl fmt::Display for DatSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.member {
+            Some(m) => write!(f, "{}", self.path.join(m).display()),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+fn has_ext(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .map(|e| exts.iter().any(|x| e.eq_ignore_ascii_case(x)))
+        .unwrap_or(false)
+}
+
+fn is_gzip(path: &Path) -> bool {
+    has_ext(path, &["gz"])
+}
+
+// 按扩展名判断是否需要加载的 DAT 文件（含压缩的 DAT）
+pub fn is_dat_file(path: &Path) -> bool {
+    has_ext(path, &["xml", "dat", "gz", "zip"])
+}
+
+// zip 中扩展名为 .xml/.dat 的成员
+fn zip_members(path: &Path) -> Result<Vec<String>> {
+    let file = File::open(path).with_context(|| format!("读取 DAT 失败: {}", path.display()))?;
+    let archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("无效的 zip 压缩包: {}", path.display()))?;
+    let mut members: Vec<String> = archive
+        .file_names()
+        .filter(|n| has_ext(Path::new(n), &["xml", "dat"]))
+        .map(str::to_string)
+        .collect();
+    members.sort();
+    Ok(members)
+}
+
+// 打开 DAT 得到解压后的文本流；zip 成员定位到其数据后直接解压，不整体读入内存
+fn open_source(source: &DatSource) -> Result<Box<dyn BufRead>> {
+    let path = source.path;
+    let file = File::open(path).with_context(|| format!("读取 DAT 失败: {}", path.display()))?;
+    if let Some(member) = source.member {
+        let mut archive = zip::ZipArchive::new(file)
+            .with_context(|| format!("无效的 zip 压缩包: {}", path.display()))?;
+        let (start, len, method) = {
+            let entry = archive
+                .by_name(member)
+                .with_context(|| format!("压缩包中没有 {}: {}", member, path.display()))?;
+            (
+                entry.data_start(),
+                entry.compressed_size(),
+                entry.compression(),
+            )
+        };
+        let mut file = archive.into_inner();
+        file.seek(SeekFrom::Start(start))
+            .with_context(|| format!("读取 DAT 失败: {}", source))?;
+        let data = file.take(len);
+        return match method {
+            CompressionMethod::Stored => Ok(Box::new(BufReader::new(data))),
+            CompressionMethod::Deflated => Ok(Box::new(BufReader::new(DeflateDecoder::new(data)))),
+            other => Err(anyhow!("不支持的压缩方式 {}: {}", other, source)),
+        };
+    }
+    if is_gzip(path) {
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))));
+    }
+    Ok(Box::new(BufReader::new(file)))
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// 跳过开头的 UTF-8 BOM，返回跳过的字节数
+fn skip_bom(input: &mut dyn BufRead, source: &DatSource) -> Result<u64> {
+    let head = input
+        .fill_buf()
+        .with_context(|| format!("读取 DAT 失败: {}", source))?;
+    if !head.starts_with(UTF8_BOM) {
+        return Ok(0);
+    }
+    input.consume(UTF8_BOM.len());
+    Ok(UTF8_BOM.len() as u64)
+}
+
+// 查看文本流开头（不消耗），第一个非空白字符是 '<' 即视为 XML
+fn detect_format(input: &mut dyn BufRead, source: &DatSource) -> Result<DatFormat> {
+    let head = input
+        .fill_buf()
+        .with_context(|| format!("读取 DAT 失败: {}", source))?;
+    let first = head.iter().find(|b| !b.is_ascii_whitespace());
+    Ok(if first == Some(&b'<') {
+        DatFormat::Xml
+    } else {
+        DatFormat::ClrMamePro
+    })
+}
+
+fn read_all(mut input: Box<dyn BufRead>, source: &DatSource) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    input
+        .read_to_end(&mut data)
+        .with_context(|| format!("读取 DAT 失败: {}", source))?;
+    Ok(data)
+}
+
+fn parse_source(source: &DatSource, parsed: &mut ParsedDat) -> Result<()> {
+    let mut input = open_source(source)?;
+    let bom = skip_bom(&mut input, source)?;
+    let (header, mut games) = match detect_format(&mut input, source)? {
+        DatFormat::Xml => xml::parse_games(input, source)?,
+        DatFormat::ClrMamePro => clrmamepro::parse_games(&read_all(input, source)?, source)?,
+    };
+    for g in games.iter_mut() {
+        // 解析器的位置从 BOM 之后开始计算
+        g.source_span = g.source_span.start + bom..g.source_span.end + bom;
+        // 没有地区、语言字段的 DAT 使用名称标签中的值
+        let tags = NameTags::parse(&g.name);
+        if g.region.is_none() && !tags.regions.is_empty() {
+            g.region = Some(tags.regions.join(", "));
+        }
+        if g.languages.is_none() && !tags.languages.is_empty() {
+            g.languages = Some(tags.languages.join(","));
+        }
+        g.tags = tags;
+        g.romanized = normalize::romanize(&g.name)
+            .or_else(|| g.description.as_deref().and_then(normalize::romanize));
+    }
+    parsed.infos.push(DatInfo {
+        file_path: source.path.display().to_string(),
+        member: source.member.map(str::to_string),
+        library: String::new(),
+        header,
+        game_count: games.len(),
+    });
+    parsed.games.extend(games);
+    Ok(())
+}
+
+// 解析一个 DAT 文件；zip 压缩包中的每个 DAT 成员分别解析后合并
+pub fn parse_dat_file(path: &Path) -> Result<ParsedDat> {
+    let mut parsed = ParsedDat::default();
+    if !is_zip(path) {
+        parse_source(&DatSource { path, member: None }, &mut parsed)?;
+        return Ok(parsed);
+    }
+    for member in zip_members(path)? {
+        let source = DatSource {
+            path,
+            member: Some(&member),
+        };
+        parse_source(&source, &mut parsed)?;
+    }
+    Ok(parsed)
+}
+
+fn read_span(input: &mut impl Read, len: u64) -> io::Result<String> {
+    let mut data = Vec::with_capacity(len as usize);
+    input.take(len).read_to_end(&mut data)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+// 按字节范围读取同一个 DAT 中多个条目的原始片段，结果与 spans 的顺序一致。
+// 普通文件直接定位；压缩的 DAT 无法定位，按起始位置排序后顺序跳读
+pub fn read_sources(
+    path: &Path,
+    member: Option<&str>,
+    spans: &[Range<u64>],
+) -> Result<Vec<String>> {
+    let source = DatSource { path, member };
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| spans[i].start);
+    let mut out = vec![String::new(); spans.len()];
+    let read_err = || format!("读取 DAT 失败: {}", source);
+
+    if member.is_none() && !is_gzip(path) {
+        let mut file = File::open(path).with_context(read_err)?;
+        for i in order {
+            let span = &spans[i];
+            file.seek(SeekFrom::Start(span.start))
+                .with_context(read_err)?;
+            out[i] = read_span(&mut file, span.end - span.start).with_context(read_err)?;
+        }
+        return Ok(out);
+    }
+
+    let mut input = open_source(&source)?;
+    let mut pos = 0u64;
+    let mut prev: Option<usize> = None;
+    for i in order {
+        let span = &spans[i];
+        // 同一条目被重复请求时复用上一次的结果
+        if let Some(p) = prev.filter(|&p| spans[p] == *span) {
+            out[i] = out[p].clone();
+            continue;
+        }
+        if span.start < pos {
+            return Err(anyhow!("条目的字节范围重叠: {}", source));
+        }
+        io::copy(&mut (&mut input).take(span.start - pos), &mut io::sink())
+            .with_context(read_err)?;
+        out[i] = read_span(&mut input, span.end - span.start).with_context(read_err)?;
+        pos = span.end;
+        prev = Some(i);
+    }
+    Ok(out)
+}
+
+// 取出单个条目的原始片段，同时返回格式以便界面选择高亮方式
+pub fn extract_game_source(game: &GameEntry) -> Result<(DatFormat, String)> {
+    let path = Path::new(&game.file_path);
+    let text = read_sources(
+        path,
+        game.dat_member.as_deref(),
+        std::slice::from_ref(&game.source_span),
+    )?
+    .pop()
+    .unwrap_or_default();
+    if text.is_empty() {
+        return Err(anyhow!("无法读取条目的原始片段: {}", game.name));
+    }
+    // 片段以 '<' 开头即为 XML，否则是 ClrMamePro 文本块
+    let format = if text.trim_start().starts_with('<') {
+        DatFormat::Xml
+    } else {
+        DatFormat::ClrMamePro
+    };
+    Ok((format, text))
+}
+
+// 把选出的条目写成 Logiqx XML DAT（用于 1G1R 等自选合集）；
+// 只有父版本也在导出列表中时才保留 cloneof
+pub fn write_logiqx(path: &Path, header: &DatHeader, games: &[&GameEntry]) -> Result<()> {
+    use quick_xml::escape::escape;
+    use std::fmt::Write as _;
+
+    let names: HashSet<&str> = games.iter().map(|g| g.name.as_str()).collect();
+    let mut out = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE datafile PUBLIC \"-//Logiqx//DTD ROM Management Datafile//EN\" \"http://www.logiqx.com/Dats/datafile.dtd\">\n<datafile>\n\t<header>\n",
+    );
+    let fields = [
+        ("name", &header.name),
+        ("description", &header.description),
+        ("version", &header.version),
+        ("date", &header.date),
+        ("author", &header.author),
+        ("homepage", &header.homepage),
+    ];
+    for (tag, value) in fields {
+        if let Some(v) = value {
+            let _ = writeln!(out, "\t\t<{0}>{1}</{0}>", tag, escape(v));
+        }
+    }
+    out.push_str("\t</header>\n");
+    for g in games {
+        let _ = write!(out, "\t<game name=\"{}\"", escape(&g.name));
+        if let Some(parent) = g.clone_of.as_deref().filter(|p| names.contains(p)) {
+            let _ = write!(out, " cloneof=\"{}\"", escape(parent));
+        }
+        out.push_str(">\n");
+        let description = g.description.as_deref().unwrap_or(&g.name);
+        let _ = writeln!(
+            out,
+            "\t\t<description>{}</description>",
+            escape(description)
+        );
+        if let Some(y) = &g.year {
+            let _ = writeln!(out, "\t\t<year>{}</year>", escape(y));
+        }
+        if let Some(m) = &g.manufacturer {
+            let _ = writeln!(out, "\t\t<manufacturer>{}</manufacturer>", escape(m));
+        }
+        for rom in &g.roms {
+            let _ = write!(out, "\t\t<rom name=\"{}\"", escape(&rom.name));
+            if let Some(size) = rom.size {
+                let _ = write!(out, " size=\"{}\"", size);
+            }
+            let hashes = [("crc", &rom.crc), ("md5", &rom.md5), ("sha1", &rom.sha1)];
+            for (attr, value) in hashes {
+                if let Some(v) = value {
+                    let _ = write!(out, " {}=\"{}\"", attr, escape(v));
+                }
+            }
+            out.push_str("/>\n");
+        }
+        out.push_str("\t</game>\n");
+    }
+    out.push_str("</datafile>\n");
+    std::fs::write(path, out).with_context(|| format!("写入 DAT 失败: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const XML: &str = "\u{feff}<?xml version=\"1.0\"?>
+<datafile>
+\t<header><name>Test - Platform</name></header>
+\t<game name=\"Alpha (USA)\"><rom name=\"a.bin\" size=\"1\" crc=\"00000001\"/></game>
+\t<game name=\"Beta (Japan)\"><rom name=\"b.bin\" size=\"2\" crc=\"00000002\"/></game>
+\t<game name=\"Gamma (Europe)\"><rom name=\"c.bin\" size=\"3\" crc=\"00000003\"/></game>
+</datafile>
+";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rgm-dat-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // 解析后按字节范围读回每个条目，读取顺序与条目顺序相反以覆盖排序
+    fn spans_and_sources(path: &Path, member: Option<&str>) -> (Vec<String>, Vec<String>) {
+        let parsed = parse_dat_file(path).unwrap();
+        let games: Vec<&GameEntry> = parsed
+            .games
+            .iter()
+            .filter(|g| g.dat_member.as_deref() == member)
+            .collect();
+        let names = games.iter().map(|g| g.name.clone()).collect();
+        let spans: Vec<Range<u64>> = games.iter().rev().map(|g| g.source_span.clone()).collect();
+        let mut sources = read_sources(path, member, &spans).unwrap();
+        sources.reverse();
+        (names, sources)
+    }
+
+    #[test]
+    fn compressed_dats_read_back_the_same_spans() {
+        let dir = temp_dir("compressed");
+        let plain = dir.join("Test - Platform.xml");
+        std::fs::write(&plain, XML).unwrap();
+
+        let gz = dir.join("Test - Platform.xml.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&gz).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(XML.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        // 一个压缩的成员和一个仅存储的成员
+        let zip_path = dir.join("dats.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        for (name, method) in [
+            ("deflated.xml", CompressionMethod::Deflated),
+            ("stored.xml", CompressionMethod::Stored),
+        ] {
+            let options = zip::write::FileOptions::default().compression_method(method);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(XML.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let (names, expected) = spans_and_sources(&plain, None);
+        assert_eq!(names, ["Alpha (USA)", "Beta (Japan)", "Gamma (Europe)"]);
+        assert!(expected[1].starts_with("<game name=\"Beta (Japan)\">"));
+        assert!(expected[1].ends_with("</game>"));
+
+        for (path, member) in [
+            (&gz, None),
+            (&zip_path, Some("deflated.xml")),
+            (&zip_path, Some("stored.xml")),
+        ] {
+            let (got_names, sources) = spans_and_sources(path, member);
+            assert_eq!(got_names, names, "{:?}", member);
+            assert_eq!(sources, expected, "{:?}", member);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,9 +2,6 @@ use eframe::egui;
 use reqwest::blocking::Client;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::path::Path; // 添加 Path 导入
-use walkdir::WalkDir; // 添加 walkdir 导入
-use std::ffi::OsStr; // 添加 OsStr 导入
 
 // 图片加载结果
 #[derive(Clone)]
@@ -32,43 +29,29 @@ impl ImageLoader {
     }
 
     // 新增：初始化 platform_map 的方法
-    pub fn initialize_platform_map(&self, xmldb_path: &Path) {
+    // 平台名称来自已加载的 DAT（优先使用 DAT 头中的名称）
+    pub fn initialize_platform_map(&self, platforms: &[String]) {
         let mut map = self.platform_map.lock().unwrap();
         map.clear(); // 清空现有映射
 
-        // 扫描 xmldb 目录
-        for entry in WalkDir::new(xmldb_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| e.path().extension() == Some(OsStr::new("xml")))
-        {
-            let path = entry.path();
-            if let Some(file_stem) = path.file_stem() {
-                let platform_name_raw = file_stem.to_string_lossy();
-                // 从文件名推断平台名称 (与 xml.rs 中的逻辑一致)
-                let platform_name = platform_name_raw
-                    .split(" (")
-                    .next()
-                    .unwrap_or(&platform_name_raw)
-                    .to_string();
-                
-                // 尝试将平台名称转换为 libretro-thumbnails 的格式
-                // 这里是一个简化的转换规则，你可能需要根据实际情况调整
-                let thumb_platform_name = platform_name
-                    .replace(" - ", "_-_")
-                    .replace(" ", "_")
-                    .replace("/", "_")
-                    .replace(":", "_");
-
-                // 插入映射 (如果尚未存在，避免覆盖)
-                map.entry(platform_name).or_insert(thumb_platform_name);
-            }
+        for platform_name in platforms {
+            // 尝试将平台名称转换为 libretro-thumbnails 的格式
+            // 这里是一个简化的转换规则，你可能需要根据实际情况调整
+            let thumb_platform_name = platform_name
+                .replace(" - ", "_-_")
+                .replace(" ", "_")
+                .replace("/", "_")
+                .replace(":", "_");
+
+            // 插入映射 (如果尚未存在，避免覆盖)
+            map.entry(platform_name.clone()).or_insert(thumb_platform_name);
         }
         
         // 你可以在这里添加一些已知的、需要特殊处理的映射
         // 例如，如果文件名推断不准确，可以手动覆盖
         // map.insert("Some Special Platform".to_string(), "Some_Special_Platform".to_string());
+        // MAME -listxml 的条目归入街机平台，对应 libretro-thumbnails 的 MAME 目录
+        map.insert(crate::xml::ARCADE_PLATFORM.to_string(), "MAME".to_string());
         
         println!("Initialized platform map with {} entries.", map.len());
     }
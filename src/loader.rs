@@ -0,0 +1,514 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use anyhow::{bail, Result};
+use eframe::egui;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::cache::{CachedDat, FileStamp, IndexCache};
+use crate::dat::{is_dat_file, parse_dat_file, ParsedDat};
+use crate::xml::{GameEntry, ParsePosition};
+
+// 后台加载线程发送给界面的消息
+pub enum LoadEvent {
+    Progress {
+        done: usize,
+        total: usize,
+    },
+    // 单个 DAT 的解析结果，界面收到后即可显示部分结果
+    Games(ParsedDat),
+    Finished {
+        platforms: Vec<String>,
+        regions: Vec<String>,
+        languages: Vec<String>,
+        status: String,
+    },
+    // 热重载：单个 DAT 的最新解析结果（文件被删除时为空）
+    FileReloaded {
+        file_path: String,
+        dat: ParsedDat,
+    },
+    // 热重载：目录被删除或移走，其中所有 DAT 的条目都应移除
+    DirRemoved {
+        dir: PathBuf,
+    },
+    ReloadFinished {
+        status: String,
+    },
+    // 单个 DAT 解析失败，不影响其他文件
+    ParseFailed(ParseFailure),
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseFailure {
+    pub file_path: String,
+    // 由外到内的错误链（with_context 添加的说明在前）
+    pub chain: Vec<String>,
+    pub position: Option<usize>,
+}
+
+impl ParseFailure {
+    fn new(path: &Path, err: &anyhow::Error) -> Self {
+        Self {
+            file_path: path.display().to_string(),
+            chain: err.chain().map(|e| e.to_string()).collect(),
+            position: err.downcast_ref::<ParsePosition>().map(|p| p.0),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct EventSender {
+    tx: Sender<LoadEvent>,
+    ctx: egui::Context,
+}
+
+impl EventSender {
+    fn send(&self, event: LoadEvent) {
+        let _ = self.tx.send(event);
+        self.ctx.request_repaint();
+    }
+}
+
+// 一个 DAT 库目录（如 No-Intro、Redump、自制 DAT）
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LibraryRoot {
+    pub name: String,
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+impl LibraryRoot {
+    // 未配置时使用当前目录下的 xmldb/
+    pub fn default_roots() -> Vec<Self> {
+        let path = std::env::current_dir()
+            .map(|d| d.join("xmldb"))
+            .unwrap_or_else(|_| PathBuf::from("xmldb"));
+        vec![Self {
+            name: "xmldb".to_string(),
+            path,
+            enabled: true,
+        }]
+    }
+
+    // 新添加的库默认以目录名命名，与已有的库重名时加上序号
+    pub fn new(existing: &[LibraryRoot], path: PathBuf) -> Self {
+        let base = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let mut name = base.clone();
+        let mut n = 1;
+        while existing.iter().any(|r| r.name == name) {
+            n += 1;
+            name = format!("{} ({})", base, n);
+        }
+        Self {
+            name,
+            path,
+            enabled: true,
+        }
+    }
+}
+
+// 库名称必须非空且互不相同（「来源」筛选按名称匹配），
+// 目录之间不能相同或互相包含，否则其中的 DAT 会被加载两次
+pub fn check_libraries<'a>(roots: impl Iterator<Item = &'a LibraryRoot>) -> Result<()> {
+    let mut seen: Vec<(&str, PathBuf)> = Vec::new();
+    for root in roots {
+        let name = root.name.trim();
+        if name.is_empty() {
+            bail!("DAT 库名称不能为空");
+        }
+        let path = canonical_path(&root.path);
+        for (other, other_path) in &seen {
+            if *other == name {
+                bail!("DAT 库名称 \"{}\" 重复", name);
+            }
+            if path.starts_with(other_path) || other_path.starts_with(&path) {
+                bail!("目录 {} 与 DAT 库 \"{}\" 重叠", root.path.display(), other);
+            }
+        }
+        seen.push((name, path));
+    }
+    Ok(())
+}
+
+// 解析符号链接后的库目录；条目的 file_path 与监视到的路径都以它为前缀，
+// 目录不存在时保持原样
+pub fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn resolve_roots(roots: &[LibraryRoot]) -> Vec<LibraryRoot> {
+    roots
+        .iter()
+        .map(|r| LibraryRoot {
+            path: canonical_path(&r.path),
+            ..r.clone()
+        })
+        .collect()
+}
+
+// 把文件监视报告的路径映射到 resolve_roots 之后的库目录下。
+// 不同平台报告的可能是配置中的路径，也可能是解析链接后的路径；
+// 已删除的文件无法 canonicalize，改为解析其所在目录
+fn event_path(roots: &[LibraryRoot], configured: &[LibraryRoot], p: &Path) -> Option<PathBuf> {
+    if library_of(roots, p).is_some() {
+        return Some(p.to_path_buf());
+    }
+    for (root, conf) in roots.iter().zip(configured) {
+        if let Ok(rest) = p.strip_prefix(&conf.path) {
+            return Some(root.path.join(rest));
+        }
+    }
+    let resolved = canonical_path(p.parent()?).join(p.file_name()?);
+    library_of(roots, &resolved).map(|_| resolved)
+}
+
+// 查找文件所属的库
+fn library_of<'a>(roots: &'a [LibraryRoot], path: &Path) -> Option<&'a LibraryRoot> {
+    roots.iter().find(|r| path.starts_with(&r.path))
+}
+
+fn tag_library(mut dat: ParsedDat, library: &str) -> ParsedDat {
+    for g in dat.games.iter_mut() {
+        if g.library != library {
+            g.library = library.to_string();
+        }
+    }
+    for info in dat.infos.iter_mut() {
+        info.library = library.to_string();
+    }
+    dat
+}
+
+// roots 只应包含启用的库
+pub fn spawn_index_loader(roots: Vec<LibraryRoot>, ctx: egui::Context) -> Receiver<LoadEvent> {
+    let (tx, rx) = mpsc::channel();
+    let sender = EventSender { tx, ctx };
+    std::thread::spawn(move || {
+        if let Err(e) = load_index(&roots, &sender) {
+            sender.send(LoadEvent::Failed(format!("{:#}", e)));
+        }
+    });
+    rx
+}
+
+// 只重新解析发生变化的 DAT
+pub fn spawn_reload(
+    paths: Vec<PathBuf>,
+    roots: Vec<LibraryRoot>,
+    ctx: egui::Context,
+) -> Receiver<LoadEvent> {
+    let (tx, rx) = mpsc::channel();
+    let sender = EventSender { tx, ctx };
+    std::thread::spawn(move || {
+        let resolved = resolve_roots(&roots);
+        // 目录事件：仍存在的目录重新解析其中所有 DAT，已消失的目录移除其下的条目
+        let mut files: Vec<PathBuf> = Vec::new();
+        for p in paths
+            .iter()
+            .filter_map(|p| event_path(&resolved, &roots, p))
+        {
+            if p.is_dir() {
+                files.extend(dat_files_in(&p));
+            } else if is_dat_file(&p) {
+                files.push(p);
+            } else if !p.exists() {
+                sender.send(LoadEvent::DirRemoved { dir: p });
+            }
+        }
+        files.sort_unstable();
+        files.dedup();
+
+        files.par_iter().for_each_with(sender.clone(), |sender, p| {
+            let Some(root) = library_of(&resolved, p) else {
+                return;
+            };
+            let dat = if p.exists() {
+                match parse_dat_file(p) {
+                    Ok(dat) => tag_library(dat, &root.name),
+                    Err(e) => {
+                        // 保留旧的条目，只报告错误
+                        sender.send(LoadEvent::ParseFailed(ParseFailure::new(p, &e)));
+                        return;
+                    }
+                }
+            } else {
+                ParsedDat::default()
+            };
+            sender.send(LoadEvent::FileReloaded {
+                file_path: p.display().to_string(),
+                dat,
+            });
+        });
+        sender.send(LoadEvent::ReloadFinished {
+            status: format!("已重新加载 {} 个 DAT 文件", files.len()),
+        });
+    });
+    rx
+}
+
+fn dat_files_in(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && is_dat_file(e.path()))
+        .map(|e| e.into_path())
+}
+
+fn load_index(roots: &[LibraryRoot], sender: &EventSender) -> Result<()> {
+    let roots = &resolve_roots(roots);
+    if roots.is_empty() {
+        sender.send(LoadEvent::Finished {
+            platforms: Vec::new(),
+            regions: Vec::new(),
+            languages: Vec::new(),
+            status: "未启用任何 DAT 库".to_string(),
+        });
+        return Ok(());
+    }
+
+    // libraries[i] 为 files[i] 所属的库名称；库目录互相包含时同一文件只加载一次，
+    // 归入先配置的库，与热重载时 library_of 的结果一致
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut libraries: Vec<&str> = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for root in roots {
+        if !root.path.exists() {
+            // 单个库不存在时不影响其他库，在诊断面板中提示
+            sender.send(LoadEvent::ParseFailed(ParseFailure {
+                file_path: root.path.display().to_string(),
+                chain: vec![format!("DAT 库 \"{}\" 的目录不存在", root.name)],
+                position: None,
+            }));
+            continue;
+        }
+        for path in dat_files_in(&root.path) {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            files.push(path);
+            libraries.push(&root.name);
+        }
+    }
+
+    if files.is_empty() {
+        sender.send(LoadEvent::Finished {
+            platforms: Vec::new(),
+            regions: Vec::new(),
+            languages: Vec::new(),
+            status: "未找到 DAT 文件".to_string(),
+        });
+        return Ok(());
+    }
+
+    // 复用 mtime 与大小都未变化的 DAT 的缓存，其余的重新解析
+    let mut cache = IndexCache::load();
+    let mut per_file: Vec<Option<CachedDat>> = files
+        .iter()
+        .map(|p| {
+            let stamp = FileStamp::of(p)?;
+            cache
+                .take_fresh(p, stamp)
+                .map(|dat| CachedDat { stamp, dat })
+        })
+        .collect();
+    let stale: Vec<usize> = (0..files.len())
+        .filter(|&i| per_file[i].is_none())
+        .collect();
+    // 缓存中剩余的条目对应已删除的文件
+    let changed = !stale.is_empty() || !cache.files.is_empty() || cache.platforms.is_empty();
+
+    let total = files.len();
+    let done = AtomicUsize::new(0);
+    let game_count = AtomicUsize::new(0);
+    let failures = AtomicUsize::new(0);
+    let report = |sender: &EventSender, i: usize, dat: Option<ParsedDat>| {
+        if let Some(dat) = dat {
+            game_count.fetch_add(dat.games.len(), Ordering::Relaxed);
+            sender.send(LoadEvent::Games(tag_library(dat, libraries[i])));
+        }
+        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+        sender.send(LoadEvent::Progress { done, total });
+    };
+
+    // 需要重写缓存时保留一份解析结果，否则直接交给界面
+    for (i, dat) in per_file.iter_mut().enumerate() {
+        let dat = match dat {
+            Some(d) if changed => d.dat.clone(),
+            Some(d) => std::mem::take(&mut d.dat),
+            None => continue,
+        };
+        report(sender, i, Some(dat));
+    }
+
+    let parsed: Vec<(usize, Option<CachedDat>)> = stale
+        .par_iter()
+        .map_with(sender.clone(), |sender, &i| {
+            let p = &files[i];
+            let dat = FileStamp::of(p).and_then(|stamp| match parse_dat_file(p) {
+                Ok(dat) => Some(CachedDat { stamp, dat }),
+                Err(e) => {
+                    failures.fetch_add(1, Ordering::Relaxed);
+                    sender.send(LoadEvent::ParseFailed(ParseFailure::new(p, &e)));
+                    None
+                }
+            });
+            report(sender, i, dat.as_ref().map(|d| d.dat.clone()));
+            (i, dat)
+        })
+        .collect();
+    for (i, dat) in parsed {
+        per_file[i] = dat;
+    }
+
+    let (platforms, regions, languages) = if changed {
+        let mut new_cache = IndexCache::new();
+        for (p, dat) in files.iter().zip(per_file) {
+            if let Some(dat) = dat {
+                new_cache.files.insert(p.display().to_string(), dat);
+            }
+        }
+        let (platforms, regions, languages) =
+            collect_facets(new_cache.files.values().flat_map(|d| d.dat.games.iter()));
+        new_cache.platforms = platforms.clone();
+        new_cache.regions = regions.clone();
+        new_cache.languages = languages.clone();
+        if let Err(e) = new_cache.save() {
+            eprintln!("写入索引缓存失败: {:#}", e);
+        }
+        (platforms, regions, languages)
+    } else {
+        (cache.platforms, cache.regions, cache.languages)
+    };
+
+    let mut status = format!(
+        "已索引平台 {} 个，游戏条目 {} 条（重新解析 {}/{} 个文件）",
+        platforms.len(),
+        game_count.load(Ordering::Relaxed),
+        stale.len(),
+        total
+    );
+    let failures = failures.load(Ordering::Relaxed);
+    if failures > 0 {
+        status.push_str(&format!("，{} 个文件解析失败", failures));
+    }
+    sender.send(LoadEvent::Finished {
+        platforms,
+        regions,
+        languages,
+        status,
+    });
+    Ok(())
+}
+
+pub fn collect_facets<'a>(
+    games: impl Iterator<Item = &'a GameEntry> + Clone,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut platforms: Vec<String> = games.clone().map(|g| g.platform.clone()).collect();
+    platforms.sort_unstable();
+    platforms.dedup();
+
+    let mut regions: Vec<String> = games
+        .clone()
+        .filter_map(|g| g.region.as_ref().map(|s| s.trim().to_string()))
+        .filter(|s| !s.is_empty())
+        .collect();
+    regions.sort_unstable();
+    regions.dedup();
+
+    let mut languages: Vec<String> = games
+        .filter_map(|g| g.languages.as_ref())
+        .flat_map(|s| s.split(','))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    languages.sort_unstable();
+    languages.dedup();
+
+    (platforms, regions, languages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rgm-loader-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn root(path: PathBuf) -> LibraryRoot {
+        LibraryRoot {
+            name: "lib".to_string(),
+            path,
+            enabled: true,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn maps_event_paths_onto_the_resolved_root() {
+        let dir = temp_dir("link");
+        std::fs::create_dir_all(dir.join("real/sub")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+        let configured = vec![root(dir.join("link"))];
+        let resolved = resolve_roots(&configured);
+        let real = canonical_path(&dir.join("real"));
+        assert_eq!(resolved[0].path, real);
+
+        // 配置中的路径、解析后的路径，以及已删除文件的路径都映射到同一个位置
+        let expected = Some(real.join("sub/a.dat"));
+        for p in [dir.join("link/sub/a.dat"), real.join("sub/a.dat")] {
+            assert_eq!(event_path(&resolved, &configured, &p), expected);
+        }
+        assert_eq!(
+            event_path(&resolved, &configured, &dir.join("other.dat")),
+            None
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_overlapping_roots_and_duplicate_names() {
+        let dir = temp_dir("overlap");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::create_dir_all(dir.join("c")).unwrap();
+        let outer = root(dir.join("a"));
+        let inner = LibraryRoot::new(std::slice::from_ref(&outer), dir.join("a/b"));
+        let other = LibraryRoot::new(&[], dir.join("c"));
+        assert!(check_libraries([&outer, &other].into_iter()).is_ok());
+        assert!(check_libraries([&outer, &inner].into_iter()).is_err());
+        assert!(check_libraries([&inner, &outer].into_iter()).is_err());
+        assert!(check_libraries([&outer, &outer].into_iter()).is_err());
+
+        // 目录不同但名称相同
+        let renamed = LibraryRoot {
+            name: outer.name.clone(),
+            ..other.clone()
+        };
+        assert!(check_libraries([&outer, &renamed].into_iter()).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn numbers_new_libraries_with_taken_names() {
+        let first = LibraryRoot::new(&[], PathBuf::from("/dats/No-Intro"));
+        let second = LibraryRoot::new(std::slice::from_ref(&first), PathBuf::from("/old/No-Intro"));
+        let third = LibraryRoot::new(
+            &[first.clone(), second.clone()],
+            PathBuf::from("/x/No-Intro"),
+        );
+        assert_eq!(first.name, "No-Intro");
+        assert_eq!(second.name, "No-Intro (2)");
+        assert_eq!(third.name, "No-Intro (3)");
+    }
+}
@@ -1,30 +1,53 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
 use eframe::{egui, App, Error};
 use rayon::prelude::*;
 use rfd::FileDialog;
-use walkdir::WalkDir;
 
+mod cache;
+mod clones;
+mod clrmamepro;
+mod dat;
 mod image_loader;
+mod loader;
+mod normalize;
+mod one_game;
+mod query;
+mod rename;
+mod result_list;
+mod result_table;
+mod search_index;
+mod tags;
+mod verify;
+mod watcher;
 mod xml;
+use crate::clones::{CloneIndex, ResultGroup};
+use crate::dat::{DatFormat, DatHeader, DatInfo};
 use crate::image_loader::{ImageLoadResult, ImageLoader};
-use crate::xml::{parse_games_from_file, GameEntry};
+use crate::loader::{LibraryRoot, LoadEvent, ParseFailure};
+use crate::one_game::Priority;
+use crate::query::{Query, QueryError};
+use crate::rename::{sanitize_filename, MatchKind, NameLookup, RenamePlan};
+use crate::result_list::{ResultList, ResultRow, RowMetrics};
+use crate::result_table::{SortKey, TableLayout};
+use crate::search_index::SearchIndex;
+use crate::tags::TagFlag;
+use crate::verify::{RomLookup, ScanProgress, ScanReport};
+use crate::watcher::DatWatcher;
+use crate::xml::GameEntry;
 use egui::Color32;
 
 // 关键词高亮辅助
-fn tokenize_query(q: &str) -> Vec<String> {
-    q.split_whitespace()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_lowercase())
-        .collect()
-}
-
 fn build_highlight_job(
     text: &str,
-    tokens: &[String],
+    spans: &[(usize, usize)],
     style: &egui::Style,
 ) -> egui::text::LayoutJob {
     use egui::text::LayoutJob;
@@ -43,26 +66,11 @@ fn build_highlight_job(
         ..Default::default()
     };
 
-    if tokens.is_empty() {
-        job.append(text, 0.0, normal);
-        return job;
-    }
-
-    let lower = text.to_lowercase();
-    let mut ranges: Vec<(usize, usize)> = Vec::new();
-    for t in tokens {
-        let mut start = 0usize;
-        while !t.is_empty() && start < lower.len() {
-            if let Some(pos) = lower[start..].find(t) {
-                let s = start + pos;
-                let e = s + t.len();
-                ranges.push((s, e));
-                start = e;
-            } else {
-                break;
-            }
-        }
-    }
+    let mut ranges: Vec<(usize, usize)> = spans
+        .iter()
+        .copied()
+        .filter(|&(s, e)| s < e && text.is_char_boundary(s) && text.is_char_boundary(e))
+        .collect();
     if ranges.is_empty() {
         job.append(text, 0.0, normal);
         return job;
@@ -326,7 +334,11 @@ fn find_bytes(hay: &[u8], start: usize, needle: &[u8]) -> Option<usize> {
     None
 }
 
+// DAT 变化后等待多久再重新解析（避免文件写入到一半）
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(default)]
 struct RecentFilters {
     platforms: Vec<String>,
     regions: Vec<String>,
@@ -335,12 +347,22 @@ struct RecentFilters {
     selected_region: Option<String>,   // 添加记住选择的区域
     selected_language: Option<String>, // 添加记住选择的语言
     default_vendors: String,           // 添加默认厂商列表
+    libraries: Vec<LibraryRoot>,       // DAT 库目录
+    selected_library: Option<String>,  // 记住选择的来源库
+    search_all_fields: bool,           // 搜索时是否匹配全部字段
+    group_clones: bool,                // 结果是否按父版本分组
+    one_game_one_rom: bool,            // 1G1R：每个游戏只保留一个条目
+    region_priority: String,           // 1G1R 地区优先级（逗号分隔）
+    language_priority: String,         // 1G1R 语言优先级（逗号分隔）
+    hidden_flags: Vec<TagFlag>,        // 隐藏带这些标记的条目
+    table_view: bool,                  // 以表格而不是卡片显示结果
+    table_layout: TableLayout,         // 表格的列、列宽与排序
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DetailTab {
     Info,
-    Xml,
+    Source,
     WebSearch,
 }
 
@@ -368,18 +390,100 @@ impl RecentFilters {
     }
 }
 
+// 决定结果列表的全部输入，与上一次相同时直接复用上次的结果
+#[derive(Clone, PartialEq)]
+struct ResultInputs {
+    index_generation: u64,
+    query: String,
+    platforms: Vec<String>,
+    region: String,
+    language: String,
+    library: String,
+    all_fields: bool,
+    hidden_flags: Vec<TagFlag>,
+    one_game_one_rom: bool,
+    region_priority: String,
+    language_priority: String,
+}
+
+// 表格中排序后的结果，以及排序时的结果和排序键
+struct SortedResults {
+    results: Arc<Vec<usize>>,
+    sort: Vec<SortKey>,
+    order: Vec<usize>,
+}
+
+// 后台重命名单个文件的结果：新文件名，以及对新文件计算的校验值（游戏没有 ROM 信息时为 None）
+struct FileRenameOutcome {
+    game: GameEntry,
+    renamed: Result<String>,
+    hashes: Option<Result<Vec<verify::FileHashes>>>,
+}
+
 struct RetroGameManagerApp {
     query: String,
+    // 解析后的查询；输入有语法错误时保留上一次成功解析的结果
+    parsed_query: Query,
+    parsed_query_text: String,
+    query_error: Option<QueryError>,
     platform_filters: Vec<String>,
     platform_search: String,
     show_platform_selector: bool,
     show_preferences: bool,
     show_about: bool,
     pending_file_rename: Option<(std::path::PathBuf, GameEntry)>,
+    // 单个文件的重命名与校验在后台线程中进行（大文件计算校验值较慢）
+    file_rename_result: Arc<Mutex<Option<FileRenameOutcome>>>,
     region_filter: String,
     language_filter: String,
+    library_filter: String,
+    // 关键字是否也匹配 DAT 中的全部字段（序列号、发行商、dump 信息等）
+    search_all_fields: bool,
+    // 结果按父版本分组，以及展开了的组（以组首的下标记录）
+    group_clones: bool,
+    expanded_groups: HashSet<usize>,
+    // 1G1R 模式及其地区、语言优先级
+    one_game_one_rom: bool,
+    region_priority: String,
+    language_priority: String,
+    // 隐藏带这些名称标记（Beta、Proto 等）的条目
+    hidden_flags: Vec<TagFlag>,
+    // 表格视图及其布局；拖动调整列宽后待保存
+    table_view: bool,
+    table_layout: TableLayout,
+    table_layout_dirty: bool,
+    // 按表格排序键排列后的结果，结果或排序键变化时重新排序
+    sorted_results: Option<SortedResults>,
     status: String,
     index: Vec<GameEntry>,
+    // 父版本/克隆关系，加载完成后按整个索引重建
+    clone_index: CloneIndex,
+    // 倒排索引；索引或父版本关系变化时 index_generation 加一
+    search_index: SearchIndex,
+    index_generation: u64,
+    // 当前结果及其对应的输入，只在输入变化时重新计算
+    results: Arc<Vec<usize>>,
+    results_inputs: Option<ResultInputs>,
+    // 结果列表的行布局，结果、分组或展开状态变化时重建
+    result_list: Option<ResultList>,
+    // 后台索引加载/重载线程（完成后为 None）
+    index_loader: Option<Receiver<LoadEvent>>,
+    // DAT 库目录，以及目录监视与热重载
+    libraries: Vec<LibraryRoot>,
+    // 当前索引所用的库（用于判断设置是否变化）
+    active_libraries: Vec<LibraryRoot>,
+    // 首选项窗口中编辑的库列表，保存时才写回 libraries
+    library_draft: Vec<LibraryRoot>,
+    library_error: Option<String>,
+    dat_watcher: Option<DatWatcher>,
+    pending_reload: HashSet<PathBuf>,
+    last_dat_change: Option<Instant>,
+    // 解析失败的 DAT 及诊断面板
+    parse_failures: Vec<ParseFailure>,
+    show_diagnostics: bool,
+    // 已加载的 DAT 及其头信息
+    dat_files: Vec<DatInfo>,
+    show_dat_files: bool,
     platforms: Vec<String>,
     available_regions: Vec<String>,
     available_languages: Vec<String>,
@@ -389,57 +493,468 @@ struct RetroGameManagerApp {
     recent_store: RecentFilters,
     // 配置选项
     default_vendors: String,
-    // 详情页状态
+    // 详情页状态（selected_index 为 index 中的下标）
     selected_index: Option<usize>,
     show_detail: bool,
-    detail_xml_cache: Option<String>,
+    detail_source_cache: Option<(DatFormat, String)>,
     detail_tab: DetailTab,
     // 图片加载器
     image_loader: Arc<ImageLoader>,
+    // 收藏校验状态
+    show_verify: bool,
+    verify_running: bool,
+    verify_progress: Arc<ScanProgress>,
+    // 完成的报告放在 Arc 中，界面每帧只克隆指针
+    verify_result: Arc<Mutex<Option<Arc<ScanReport>>>>,
+    // 批量重命名状态
+    show_batch_rename: bool,
+    rename_progress: Arc<ScanProgress>,
+    rename_plan: Arc<Mutex<Option<RenamePlan>>>,
     // 初始化标志
     initialized: bool,
 }
 
 impl RetroGameManagerApp {
-    fn new(cc: &eframe::CreationContext<'_>) -> Result<Self> {
-        let xmldb_dir = std::env::current_dir()
-            .context("无法获取当前目录")?
-            .join("xmldb");
-        let (index, platforms, regions, languages, status) = load_index(&xmldb_dir)?;
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
         let persisted = RecentFilters::load();
         install_chinese_fonts(&cc.egui_ctx);
         // 创建 ImageLoader 实例
         let image_loader = Arc::new(ImageLoader::new());
-        
-        // 初始化 platform_map
-        image_loader.initialize_platform_map(&xmldb_dir);
-        Ok(Self {
+        let libraries = if persisted.libraries.is_empty() {
+            LibraryRoot::default_roots()
+        } else {
+            persisted.libraries.clone()
+        };
+
+        let mut app = Self {
             query: String::new(),
+            parsed_query: Query::default(),
+            parsed_query_text: String::new(),
+            query_error: None,
             platform_filters: persisted.selected_platforms.clone(),
             platform_search: String::new(),
             show_platform_selector: false,
             show_preferences: false,
             show_about: false,
             pending_file_rename: None,
+            file_rename_result: Arc::new(Mutex::new(None)),
             region_filter: persisted.selected_region.clone().unwrap_or_default(),
             language_filter: persisted.selected_language.clone().unwrap_or_default(),
+            library_filter: persisted.selected_library.clone().unwrap_or_default(),
+            search_all_fields: persisted.search_all_fields,
+            group_clones: persisted.group_clones,
+            expanded_groups: HashSet::new(),
+            one_game_one_rom: persisted.one_game_one_rom,
+            region_priority: persisted.region_priority.clone(),
+            language_priority: persisted.language_priority.clone(),
+            hidden_flags: persisted.hidden_flags.clone(),
+            table_view: persisted.table_view,
+            table_layout: {
+                let mut layout = persisted.table_layout.clone();
+                layout.normalize();
+                layout
+            },
+            table_layout_dirty: false,
+            sorted_results: None,
             default_vendors: persisted.default_vendors.clone(),
-            status,
-            platforms,
-            available_regions: regions,
-            available_languages: languages,
+            status: String::new(),
+            platforms: Vec::new(),
+            available_regions: Vec::new(),
+            available_languages: Vec::new(),
             recent_platforms: persisted.platforms.clone(),
             recent_regions: persisted.regions.clone(),
             recent_languages: persisted.languages.clone(),
             recent_store: persisted,
-            index,
+            index: Vec::new(),
+            clone_index: CloneIndex::default(),
+            search_index: SearchIndex::default(),
+            index_generation: 0,
+            results: Arc::new(Vec::new()),
+            results_inputs: None,
+            result_list: None,
+            index_loader: None,
+            libraries,
+            active_libraries: Vec::new(),
+            library_draft: Vec::new(),
+            library_error: None,
+            dat_watcher: None,
+            pending_reload: HashSet::new(),
+            last_dat_change: None,
+            parse_failures: Vec::new(),
+            show_diagnostics: false,
+            dat_files: Vec::new(),
+            show_dat_files: false,
             selected_index: None,
             show_detail: false,
-            detail_xml_cache: None,
+            detail_source_cache: None,
             detail_tab: DetailTab::Info,
             image_loader, // 初始化图片加载器
+            show_verify: false,
+            verify_running: false,
+            verify_progress: Arc::new(ScanProgress::default()),
+            verify_result: Arc::new(Mutex::new(None)),
+            show_batch_rename: false,
+            rename_progress: Arc::new(ScanProgress::default()),
+            rename_plan: Arc::new(Mutex::new(None)),
             initialized: false,
-        })
+        };
+        app.reload_libraries(&cc.egui_ctx);
+        app
+    }
+
+    fn enabled_libraries(&self) -> Vec<LibraryRoot> {
+        self.libraries
+            .iter()
+            .filter(|r| r.enabled)
+            .cloned()
+            .collect()
+    }
+
+    // 清空索引，从所有启用的 DAT 库重新加载，并重新监视这些目录
+    fn reload_libraries(&mut self, ctx: &egui::Context) {
+        let roots = self.enabled_libraries();
+        self.active_libraries = roots.clone();
+        self.index.clear();
+        self.clone_index = CloneIndex::default();
+        self.search_index = SearchIndex::default();
+        self.index_generation += 1;
+        self.expanded_groups.clear();
+        self.platforms.clear();
+        self.available_regions.clear();
+        self.available_languages.clear();
+        self.parse_failures.clear();
+        self.dat_files.clear();
+        self.pending_reload.clear();
+        self.selected_index = None;
+        self.show_detail = false;
+        self.status = "正在加载 DAT…".to_string();
+
+        // 监视解析链接后的目录，使报告的路径与条目的 file_path 一致
+        let dirs: Vec<PathBuf> = roots
+            .iter()
+            .map(|r| loader::canonical_path(&r.path))
+            .collect();
+        self.dat_watcher = match DatWatcher::new(&dirs, ctx.clone()) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                eprintln!("无法监视 DAT 目录: {:#}", e);
+                None
+            }
+        };
+        // 在后台线程中加载索引，加载完成后再初始化 platform_map
+        self.index_loader = Some(loader::spawn_index_loader(roots, ctx.clone()));
+    }
+
+    // 收集变化的 DAT，等待一段时间没有新变化后再重新解析
+    fn poll_dat_watcher(&mut self, ctx: &egui::Context) {
+        if let Some(watcher) = &self.dat_watcher {
+            let changed = watcher.changed_files();
+            if !changed.is_empty() {
+                self.pending_reload.extend(changed);
+                self.last_dat_change = Some(Instant::now());
+            }
+        }
+        // 初次加载或上一次重载尚未完成时先保留待处理的文件
+        if self.pending_reload.is_empty() || self.index_loader.is_some() {
+            return;
+        }
+        let quiet = self
+            .last_dat_change
+            .map(|t| t.elapsed())
+            .unwrap_or(RELOAD_DELAY);
+        if quiet < RELOAD_DELAY {
+            ctx.request_repaint_after(RELOAD_DELAY - quiet);
+            return;
+        }
+        let paths: Vec<PathBuf> = self.pending_reload.drain().collect();
+        self.status = format!("检测到 {} 个 DAT 变化，正在重新加载…", paths.len());
+        self.index_loader = Some(loader::spawn_reload(
+            paths,
+            self.enabled_libraries(),
+            ctx.clone(),
+        ));
+    }
+
+    // 接收后台加载线程的消息，边加载边显示部分结果
+    fn poll_index_loader(&mut self) {
+        let Some(rx) = &self.index_loader else {
+            return;
+        };
+        let events: Vec<LoadEvent> = rx.try_iter().collect();
+        for event in events {
+            match event {
+                LoadEvent::Progress { done, total } => {
+                    self.status = format!(
+                        "正在加载 DAT：已解析 {}/{} 个文件，游戏条目 {} 条",
+                        done,
+                        total,
+                        self.index.len()
+                    );
+                }
+                LoadEvent::Games(dat) => {
+                    let (platforms, regions, languages) = loader::collect_facets(dat.games.iter());
+                    merge_sorted(&mut self.platforms, platforms);
+                    merge_sorted(&mut self.available_regions, regions);
+                    merge_sorted(&mut self.available_languages, languages);
+                    self.index.extend(dat.games);
+                    self.search_index.extend(&self.index);
+                    self.index_generation += 1;
+                    self.dat_files.extend(dat.infos);
+                }
+                LoadEvent::Finished {
+                    platforms,
+                    regions,
+                    languages,
+                    status,
+                } => {
+                    self.platforms = platforms;
+                    self.available_regions = regions;
+                    self.available_languages = languages;
+                    self.status = status;
+                    self.index_loader = None;
+                    self.image_loader.initialize_platform_map(&self.platforms);
+                    self.rebuild_clone_index();
+                }
+                LoadEvent::FileReloaded { file_path, dat } => {
+                    // 替换该文件的条目，并按 (文件, 名称) 找回当前选中的游戏
+                    let selected = self
+                        .selected_index
+                        .and_then(|i| self.index.get(i))
+                        .map(|g| (g.file_path.clone(), g.name.clone()));
+                    self.index.retain(|g| g.file_path != file_path);
+                    self.index.extend(dat.games);
+                    // 下标已经变化，重载结束后再重建；在此之前搜索按线性扫描
+                    self.clone_index = CloneIndex::default();
+                    self.search_index = SearchIndex::default();
+                    self.index_generation += 1;
+                    self.dat_files.retain(|d| d.file_path != file_path);
+                    self.dat_files.extend(dat.infos);
+                    self.parse_failures.retain(|f| f.file_path != file_path);
+                    self.selected_index = selected.and_then(|(f, n)| {
+                        self.index
+                            .iter()
+                            .position(|g| g.file_path == f && g.name == n)
+                    });
+                    self.detail_source_cache = None;
+                }
+                LoadEvent::DirRemoved { dir } => {
+                    let removed = |file_path: &str| Path::new(file_path).starts_with(&dir);
+                    let selected = self
+                        .selected_index
+                        .and_then(|i| self.index.get(i))
+                        .filter(|g| !removed(&g.file_path))
+                        .map(|g| (g.file_path.clone(), g.name.clone()));
+                    self.index.retain(|g| !removed(&g.file_path));
+                    self.clone_index = CloneIndex::default();
+                    self.search_index = SearchIndex::default();
+                    self.index_generation += 1;
+                    self.dat_files.retain(|d| !removed(&d.file_path));
+                    self.parse_failures.retain(|f| !removed(&f.file_path));
+                    self.selected_index = selected.and_then(|(f, n)| {
+                        self.index
+                            .iter()
+                            .position(|g| g.file_path == f && g.name == n)
+                    });
+                    self.detail_source_cache = None;
+                }
+                LoadEvent::ReloadFinished { status } => {
+                    let (platforms, regions, languages) = loader::collect_facets(self.index.iter());
+                    self.platforms = platforms;
+                    self.available_regions = regions;
+                    self.available_languages = languages;
+                    self.status = status;
+                    self.index_loader = None;
+                    self.image_loader.initialize_platform_map(&self.platforms);
+                    self.rebuild_clone_index();
+                    self.search_index = SearchIndex::build(&self.index);
+                }
+                LoadEvent::ParseFailed(failure) => {
+                    self.parse_failures
+                        .retain(|f| f.file_path != failure.file_path);
+                    self.parse_failures.push(failure);
+                }
+                LoadEvent::Failed(e) => {
+                    self.status = format!("加载索引失败: {}", e);
+                    self.index_loader = None;
+                }
+            }
+        }
+    }
+
+    // 搜索框内容变化时重新解析查询
+    fn update_query(&mut self) {
+        if self.query == self.parsed_query_text {
+            return;
+        }
+        self.parsed_query_text = self.query.clone();
+        match Query::parse(&self.query) {
+            Ok(q) => {
+                self.parsed_query = q;
+                self.query_error = None;
+            }
+            Err(e) => self.query_error = Some(e),
+        }
+    }
+
+    fn rebuild_clone_index(&mut self) {
+        self.clone_index = CloneIndex::build(&self.index);
+        self.expanded_groups.clear();
+        self.index_generation += 1;
+    }
+
+    // 查询、筛选条件或索引变化时重新计算结果
+    fn refresh_results(&mut self) {
+        let inputs = ResultInputs {
+            index_generation: self.index_generation,
+            query: self.parsed_query_text.clone(),
+            platforms: self.platform_filters.clone(),
+            region: self.region_filter.clone(),
+            language: self.language_filter.clone(),
+            library: self.library_filter.clone(),
+            all_fields: self.search_all_fields,
+            hidden_flags: self.hidden_flags.clone(),
+            one_game_one_rom: self.one_game_one_rom,
+            region_priority: self.region_priority.clone(),
+            language_priority: self.language_priority.clone(),
+        };
+        if self.results_inputs.as_ref() == Some(&inputs) {
+            return;
+        }
+        let mut results = filter_results(
+            &self.index,
+            &self.search_index,
+            &self.parsed_query,
+            &self.platform_filters, // 传递平台过滤器数组
+            &self.region_filter,
+            &self.language_filter,
+            &self.library_filter,
+            self.search_all_fields,
+        );
+        if !self.hidden_flags.is_empty() {
+            results.retain(|&i| !self.index[i].tags.has_any(&self.hidden_flags));
+        }
+        if self.one_game_one_rom {
+            let priority = Priority::parse(&self.region_priority, &self.language_priority);
+            results = one_game::select(&self.index, &results, &self.clone_index, &priority);
+        }
+        self.results = Arc::new(results);
+        self.results_inputs = Some(inputs);
+    }
+
+    // 表格视图：点击表头排序（按住 Shift 追加排序键），拖动表头边缘调整列宽
+    fn results_table(&mut self, ui: &mut egui::Ui, results: &Arc<Vec<usize>>) {
+        let sorted = self
+            .sorted_results
+            .as_ref()
+            .is_some_and(|s| Arc::ptr_eq(&s.results, results) && s.sort == self.table_layout.sort);
+        if !sorted {
+            self.sorted_results = Some(SortedResults {
+                results: Arc::clone(results),
+                sort: self.table_layout.sort.clone(),
+                order: result_table::sort_results(&self.index, results, &self.table_layout.sort),
+            });
+        }
+        let Some(SortedResults { order, .. }) = self.sorted_results.as_ref() else {
+            return;
+        };
+
+        let columns: Vec<_> = self.table_layout.visible().cloned().collect();
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let mut clicked_header = None;
+        let mut clicked_row = None;
+        let mut widths = Vec::new();
+        // 可见列变化时使用新的表格状态，列宽从保存的布局中读取
+        let table_id: Vec<_> = columns.iter().map(|c| c.column).collect();
+        ui.push_id(table_id, |ui| {
+            let mut table = egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .sense(egui::Sense::click())
+                .auto_shrink([false, false]);
+            for (i, c) in columns.iter().enumerate() {
+                let column = if i + 1 == columns.len() {
+                    egui_extras::Column::remainder()
+                } else {
+                    egui_extras::Column::initial(c.width)
+                };
+                table = table.column(column.at_least(40.0).clip(true));
+            }
+            table
+                .header(row_height + 6.0, |mut header| {
+                    for c in &columns {
+                        header.col(|ui| {
+                            let label = match self.table_layout.sort_of(c.column) {
+                                Some((rank, descending)) => {
+                                    let arrow = if descending { "⏷" } else { "⏶" };
+                                    if self.table_layout.sort.len() > 1 {
+                                        format!("{} {}{}", c.column.label(), arrow, rank + 1)
+                                    } else {
+                                        format!("{} {}", c.column.label(), arrow)
+                                    }
+                                }
+                                None => c.column.label().to_string(),
+                            };
+                            let response = ui
+                                .add(
+                                    egui::Button::new(egui::RichText::new(label).strong())
+                                        .frame(false),
+                                )
+                                .on_hover_text("单击排序，按住 Shift 单击追加排序列");
+                            if response.clicked() {
+                                let additive = ui.input(|i| i.modifiers.shift);
+                                clicked_header = Some((c.column, additive));
+                            }
+                        });
+                    }
+                })
+                .body(|body| {
+                    widths = body.widths().to_vec();
+                    body.rows(row_height, order.len(), |mut row| {
+                        let gi = order[row.index()];
+                        let g = &self.index[gi];
+                        row.set_selected(self.selected_index == Some(gi));
+                        for c in &columns {
+                            row.col(|ui| {
+                                let text = c.column.value(g).unwrap_or("");
+                                ui.add(egui::Label::new(text).truncate(true).selectable(false));
+                            });
+                        }
+                        if row.response().clicked() {
+                            clicked_row = Some(gi);
+                        }
+                    });
+                });
+        });
+
+        if let Some((column, additive)) = clicked_header {
+            self.table_layout.click_header(column, additive);
+            self.persist_recents();
+        }
+        if let Some(gi) = clicked_row {
+            self.selected_index = Some(gi);
+            self.show_detail = true;
+            self.detail_source_cache = None;
+            self.detail_tab = DetailTab::Info;
+        }
+        // 记录调整后的列宽（最后一列填满剩余宽度，不记录），松开鼠标后再保存
+        let last = columns.len().saturating_sub(1);
+        for (c, &width) in columns.iter().zip(&widths).take(last) {
+            if let Some(state) = self
+                .table_layout
+                .columns
+                .iter_mut()
+                .find(|s| s.column == c.column)
+            {
+                if (state.width - width).abs() > 0.5 {
+                    state.width = width;
+                    self.table_layout_dirty = true;
+                }
+            }
+        }
+        if self.table_layout_dirty && !ui.input(|i| i.pointer.any_down()) {
+            self.table_layout_dirty = false;
+            self.persist_recents();
+        }
     }
 
     fn persist_recents(&mut self) {
@@ -461,12 +976,67 @@ impl RetroGameManagerApp {
         // 保存常用平台配置
         self.recent_store.default_vendors = self.default_vendors.clone();
 
+        // 保存 DAT 库配置与来源筛选
+        self.recent_store.libraries = self.libraries.clone();
+        self.recent_store.selected_library = if self.library_filter.is_empty() {
+            None
+        } else {
+            Some(self.library_filter.clone())
+        };
+        self.recent_store.search_all_fields = self.search_all_fields;
+        self.recent_store.group_clones = self.group_clones;
+        self.recent_store.one_game_one_rom = self.one_game_one_rom;
+        self.recent_store.region_priority = self.region_priority.clone();
+        self.recent_store.language_priority = self.language_priority.clone();
+        self.recent_store.hidden_flags = self.hidden_flags.clone();
+        self.recent_store.table_view = self.table_view;
+        self.recent_store.table_layout = self.table_layout.clone();
+
         self.recent_store.save();
     }
+
+    // 在后台线程中校验所选目录下的 ROM 收藏
+    fn start_verify(&mut self, ctx: &egui::Context, root: PathBuf) {
+        let lookup = RomLookup::build(&self.index);
+        let progress = Arc::new(ScanProgress::default());
+        let result = Arc::clone(&self.verify_result);
+        *result.lock().unwrap() = None;
+        self.verify_progress = Arc::clone(&progress);
+        self.verify_running = true;
+        self.show_verify = true;
+
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let report = verify::scan_collection(&root, &lookup, &progress);
+            *result.lock().unwrap() = Some(Arc::new(report));
+            ctx.request_repaint();
+        });
+    }
+
+    // 在后台线程中为所选目录生成批量重命名预览
+    fn start_batch_rename(&mut self, ctx: &egui::Context, root: PathBuf) {
+        let lookup = RomLookup::build(&self.index);
+        let names = NameLookup::build(&self.index);
+        let progress = Arc::new(ScanProgress::default());
+        let plan = Arc::clone(&self.rename_plan);
+        *plan.lock().unwrap() = None;
+        self.rename_progress = Arc::clone(&progress);
+        self.show_batch_rename = true;
+
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = rename::build_plan(&root, &lookup, &names, &progress);
+            *plan.lock().unwrap() = Some(result);
+            ctx.request_repaint();
+        });
+    }
 }
 
 impl App for RetroGameManagerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_index_loader();
+        self.poll_dat_watcher(ctx);
+
         // 初始化逻辑
         if !self.initialized {
             // 注意：我们不再自动选中常用平台
@@ -480,8 +1050,40 @@ impl App for RetroGameManagerApp {
             egui::menu::bar(ui, |ui| {
                 // Mac标准菜单排列：首选项、关于
                 if ui.button("首选项").clicked() {
+                    self.library_draft = self.libraries.clone();
+                    self.library_error = None;
                     self.show_preferences = true;
                 }
+                if ui
+                    .add_enabled(!self.verify_running, egui::Button::new("校验收藏"))
+                    .clicked()
+                {
+                    if let Some(dir) = FileDialog::new().pick_folder() {
+                        self.start_verify(ctx, dir);
+                    }
+                }
+                let has_report = self.verify_result.lock().unwrap().is_some();
+                if ui
+                    .add_enabled(has_report, egui::Button::new("校验报告"))
+                    .clicked()
+                {
+                    self.show_verify = true;
+                }
+                if ui.button("批量重命名").clicked() {
+                    if let Some(dir) = FileDialog::new().pick_folder() {
+                        self.start_batch_rename(ctx, dir);
+                    }
+                }
+                if ui.button("DAT 文件").clicked() {
+                    self.show_dat_files = true;
+                }
+                if !self.parse_failures.is_empty()
+                    && ui
+                        .button(format!("诊断 ({})", self.parse_failures.len()))
+                        .clicked()
+                {
+                    self.show_diagnostics = true;
+                }
                 if ui.button("关于").clicked() {
                     self.show_about = true;
                 }
@@ -492,7 +1094,48 @@ impl App for RetroGameManagerApp {
         egui::TopBottomPanel::top("search").show(ctx, |ui| {
             ui.horizontal_wrapped(|ui| {
                 ui.label("搜索");
-                let _changed = ui.text_edit_singleline(&mut self.query).changed();
+                ui.text_edit_singleline(&mut self.query)
+                    .on_hover_text(query::HELP);
+                if ui
+                    .checkbox(&mut self.search_all_fields, "搜索全部字段")
+                    .changed()
+                {
+                    self.persist_recents();
+                }
+                if ui
+                    .checkbox(&mut self.group_clones, "按父版本分组")
+                    .changed()
+                {
+                    self.persist_recents();
+                }
+                if ui.checkbox(&mut self.table_view, "表格视图").changed() {
+                    self.persist_recents();
+                }
+                if self.table_view {
+                    ui.menu_button("列", |ui| {
+                        let mut changed = false;
+                        let visible = self.table_layout.visible().count();
+                        for column in self.table_layout.columns.iter_mut() {
+                            // 至少保留一列
+                            let locked = column.visible && visible == 1;
+                            let checkbox =
+                                egui::Checkbox::new(&mut column.visible, column.column.label());
+                            changed |= ui.add_enabled(!locked, checkbox).changed();
+                        }
+                        if changed {
+                            self.persist_recents();
+                        }
+                    });
+                }
+                if ui
+                    .checkbox(&mut self.one_game_one_rom, "1G1R")
+                    .on_hover_text(
+                        "每个游戏只保留一个条目，按首选项中的地区、语言优先级和修订号挑选",
+                    )
+                    .changed()
+                {
+                    self.persist_recents();
+                }
                 ui.separator();
 
                 // 添加键盘快捷键提示
@@ -784,6 +1427,69 @@ impl App for RetroGameManagerApp {
 
                 ui.separator();
 
+                // 按名称标记隐藏条目
+                ui.horizontal(|ui| {
+                    ui.label("隐藏");
+                    let text = if self.hidden_flags.is_empty() {
+                        "无".to_string()
+                    } else {
+                        format!("{} 项", self.hidden_flags.len())
+                    };
+                    egui::ComboBox::from_id_source("hidden_flags_combo")
+                        .selected_text(text)
+                        .show_ui(ui, |ui| {
+                            let mut changed = false;
+                            for flag in TagFlag::ALL {
+                                let mut hidden = self.hidden_flags.contains(&flag);
+                                if ui.checkbox(&mut hidden, flag.label()).changed() {
+                                    if hidden {
+                                        self.hidden_flags.push(flag);
+                                    } else {
+                                        self.hidden_flags.retain(|f| *f != flag);
+                                    }
+                                    changed = true;
+                                }
+                            }
+                            if changed {
+                                self.persist_recents();
+                            }
+                        });
+                });
+
+                ui.separator();
+
+                // 来源库（左侧标签）
+                ui.horizontal(|ui| {
+                    ui.label("来源");
+                    egui::ComboBox::from_id_source("library_combo")
+                        .selected_text(if self.library_filter.is_empty() {
+                            "全部".to_string()
+                        } else {
+                            self.library_filter.clone()
+                        })
+                        .show_ui(ui, |ui| {
+                            let mut changed = false;
+                            if ui
+                                .selectable_label(self.library_filter.is_empty(), "全部")
+                                .clicked()
+                            {
+                                self.library_filter.clear();
+                                changed = true;
+                            }
+                            ui.separator();
+                            for lib in self.libraries.iter().filter(|r| r.enabled) {
+                                let selected = self.library_filter == lib.name;
+                                if ui.selectable_label(selected, &lib.name).clicked() {
+                                    self.library_filter = lib.name.clone();
+                                    changed = true;
+                                }
+                            }
+                            if changed {
+                                self.persist_recents();
+                            }
+                        });
+                });
+
                 // 清空按钮区
                 ui.separator();
                 if ui.button("全部清空").clicked() {
@@ -791,27 +1497,69 @@ impl App for RetroGameManagerApp {
                     self.platform_filters.clear();
                     self.region_filter.clear();
                     self.language_filter.clear();
+                    self.library_filter.clear();
                 }
             });
+            // 查询语法错误显示在搜索框下方
+            self.update_query();
+            if let Some(err) = &self.query_error {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!("查询语法错误（{}），暂用上一次有效的查询", err),
+                );
+            }
         });
 
-        let results = filter_results(
-            &self.index,
-            &self.query,
-            &self.platform_filters, // 传递平台过滤器数组
-            &self.region_filter,
-            &self.language_filter,
-        );
+        self.refresh_results();
+        let results = Arc::clone(&self.results);
 
         egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
-            ui.label(format!("{} | 结果: {} 条", self.status, results.len()));
+            ui.horizontal(|ui| {
+                ui.label(format!("{} | 结果: {} 条", self.status, results.len()));
+                if ui
+                    .add_enabled(!results.is_empty(), egui::Button::new("复制结果源码"))
+                    .clicked()
+                {
+                    match collect_sources(&self.index, &results) {
+                        Ok(text) => {
+                            ctx.output_mut(|o| o.copied_text = text);
+                            self.status = format!("已复制 {} 个条目的源码", results.len());
+                        }
+                        Err(e) => self.status = format!("复制源码失败: {:#}", e),
+                    }
+                }
+                if ui
+                    .add_enabled(!results.is_empty(), egui::Button::new("导出 DAT…"))
+                    .on_hover_text("把当前结果导出为 Logiqx XML DAT")
+                    .clicked()
+                {
+                    let file_name = if self.one_game_one_rom {
+                        "1G1R.xml"
+                    } else {
+                        "export.xml"
+                    };
+                    if let Some(path) = FileDialog::new()
+                        .add_filter("DAT", &["xml", "dat"])
+                        .set_file_name(file_name)
+                        .save_file()
+                    {
+                        match export_results(&self.index, &results, &path, self.one_game_one_rom) {
+                            Ok(()) => {
+                                self.status =
+                                    format!("已导出 {} 个条目到 {}", results.len(), path.display())
+                            }
+                            Err(e) => self.status = format!("导出失败: {:#}", e),
+                        }
+                    }
+                }
+            });
         });
 
         // 详情窗口（单独窗口显示）
         if self.show_detail {
             let mut open = true;
             if let Some(sel) = self.selected_index {
-                if let Some(&g) = results.get(sel) {
+                if let Some(g) = self.index.get(sel) {
                     let title = g.name.to_string();
                     egui::Window::new(title)
                         .open(&mut open)
@@ -821,10 +1569,10 @@ impl App for RetroGameManagerApp {
                         .show(ctx, |ui| {
                             ui.horizontal(|ui| {
                                 let info_clicked = ui.selectable_label(self.detail_tab == DetailTab::Info, "基本信息").clicked();
-                                let xml_clicked = ui.selectable_label(self.detail_tab == DetailTab::Xml, "XML 源码").clicked();
+                                let source_clicked = ui.selectable_label(self.detail_tab == DetailTab::Source, "源码").clicked();
                                 let web_search_clicked = ui.selectable_label(self.detail_tab == DetailTab::WebSearch, "网页搜索").clicked();
                                 if info_clicked { self.detail_tab = DetailTab::Info; }
-                                if xml_clicked { self.detail_tab = DetailTab::Xml; }
+                                if source_clicked { self.detail_tab = DetailTab::Source; }
                                 if web_search_clicked { self.detail_tab = DetailTab::WebSearch; }
                             });
                             ui.separator();
@@ -834,10 +1582,15 @@ impl App for RetroGameManagerApp {
                                     ui.heading(&g.name);
                                     
                                     // 加载并显示图片
+                                    // 街机的缩略图按描述（完整标题）命名，其他平台使用游戏名
+                                    let image_name = match &g.description {
+                                        Some(d) if g.platform == crate::xml::ARCADE_PLATFORM => d.clone(),
+                                        _ => g.name.clone(),
+                                    };
                                     let (boxart, title, snap) = self.image_loader.load_game_images_async(
                                         ctx,
                                         g.platform.clone(),
-                                        g.name.clone(), // 使用游戏名加载图片
+                                        image_name,
                                     );
                                     
                                     // 只有当至少有一张图片加载成功时，才显示图片行
@@ -936,22 +1689,120 @@ impl App for RetroGameManagerApp {
                                     ui.label(format!("区域: {}", g.region.as_deref().unwrap_or("未知")));
                                     ui.label(format!("语言: {}", g.languages.as_deref().unwrap_or("未知")));
                                     if let Some(a) = &g.archive_name { ui.label(format!("归档名: {}", a)); }
+                                    if let Some(d) = &g.description { ui.label(format!("描述: {}", d)); }
+                                    if let Some(y) = &g.year { ui.label(format!("年份: {}", y)); }
+                                    if let Some(m) = &g.manufacturer { ui.label(format!("厂商: {}", m)); }
+                                    if let Some(c) = &g.clone_of { ui.label(format!("父版本: {}", c)); }
+                                    if let Some(r) = g.rom_of.as_ref().filter(|r| Some(*r) != g.clone_of.as_ref()) { ui.label(format!("共享 ROM: {}", r)); }
+                                    if let Some(d) = &g.driver_status { ui.label(format!("驱动状态: {}", d)); }
+                                    if let Some(r) = &g.tags.revision { ui.label(format!("修订: {}", r)); }
+                                    if !g.tags.flags.is_empty() { ui.label(format!("标记: {}", flag_labels(&g.tags.flags))); }
+                                    ui.label(format!("来源库: {}", g.library));
                                     ui.label(format!("来源文件: {}", g.file_path));
+                                    if let Some(m) = &g.dat_member { ui.label(format!("压缩包成员: {}", m)); }
+
+                                    // 同一父版本下的其他版本，点击即切换到该条目
+                                    let siblings = self.clone_index.siblings(sel);
+                                    if !siblings.is_empty() {
+                                        ui.separator();
+                                        ui.label(format!("其他版本 ({} 个)", siblings.len()));
+                                        for s in siblings {
+                                            let Some(other) = self.index.get(s) else { continue };
+                                            let mut text = format!("{} [{}]", other.name, other.region.as_deref().unwrap_or("未知"));
+                                            if self.clone_index.is_parent_of(s, sel) { text.push_str(" (父版本)"); }
+                                            if ui.link(text).clicked() {
+                                                self.selected_index = Some(s);
+                                                self.detail_source_cache = None;
+                                            }
+                                        }
+                                    }
+
+                                    // ROM 校验信息
+                                    if !g.roms.is_empty() {
+                                        ui.separator();
+                                        ui.label(format!("ROM 文件 ({} 个)", g.roms.len()));
+                                        egui::Grid::new("rom_grid")
+                                            .striped(true)
+                                            .num_columns(5)
+                                            .show(ui, |ui| {
+                                                ui.strong("文件名");
+                                                ui.strong("大小");
+                                                ui.strong("CRC32");
+                                                ui.strong("MD5");
+                                                ui.strong("SHA1");
+                                                ui.end_row();
+                                                for rom in &g.roms {
+                                                    ui.label(&rom.name);
+                                                    ui.label(rom.size.map(|s| s.to_string()).unwrap_or_default());
+                                                    ui.monospace(rom.crc.as_deref().unwrap_or("-"));
+                                                    ui.monospace(rom.md5.as_deref().unwrap_or("-"));
+                                                    ui.monospace(rom.sha1.as_deref().unwrap_or("-"));
+                                                    ui.end_row();
+                                                }
+                                            });
+                                    }
+                                    if !g.disks.is_empty() {
+                                        ui.separator();
+                                        ui.label(format!("磁盘镜像 ({} 个)", g.disks.len()));
+                                        egui::Grid::new("disk_grid")
+                                            .striped(true)
+                                            .num_columns(2)
+                                            .show(ui, |ui| {
+                                                ui.strong("名称");
+                                                ui.strong("SHA1");
+                                                ui.end_row();
+                                                for disk in &g.disks {
+                                                    ui.label(&disk.name);
+                                                    ui.monospace(disk.sha1.as_deref().unwrap_or("-"));
+                                                    ui.end_row();
+                                                }
+                                            });
+                                    }
+                                    // DAT 中的全部字段，按元素分组显示
+                                    if !g.fields.is_empty() {
+                                        ui.separator();
+                                        ui.label("全部字段");
+                                        for (si, section) in g.fields.iter().enumerate() {
+                                            egui::CollapsingHeader::new(&section.name)
+                                                .id_source(("field_section", si))
+                                                .default_open(true)
+                                                .show(ui, |ui| {
+                                                    egui::Grid::new(("field_grid", si))
+                                                        .striped(true)
+                                                        .num_columns(2)
+                                                        .show(ui, |ui| {
+                                                            for (k, v) in &section.values {
+                                                                ui.strong(k);
+                                                                ui.label(v);
+                                                                ui.end_row();
+                                                            }
+                                                        });
+                                                });
+                                        }
+                                    }
                                 }
-                                DetailTab::Xml => {
-                                    if self.detail_xml_cache.is_none() {
-                                        let p = PathBuf::from(&g.file_path);
-                                        if let Ok(xml) = crate::xml::extract_game_xml_by_index(&p, g.game_idx) {
-                                            self.detail_xml_cache = Some(xml);
+                                DetailTab::Source => {
+                                    if self.detail_source_cache.is_none() {
+                                        if let Ok(source) = crate::dat::extract_game_source(g) {
+                                            self.detail_source_cache = Some(source);
                                         }
                                     }
-                                    let code_txt = self.detail_xml_cache.clone().unwrap_or_else(|| "<game/>".to_string());
+                                    let (format, code_txt) = self.detail_source_cache.clone().unwrap_or((DatFormat::Xml, "<game/>".to_string()));
                                     egui::ScrollArea::both() // 启用水平和垂直滚动
                                         .auto_shrink([false, true])
                                         .max_height(ui.available_height() - 10.0)
                                         .show(ui, |ui| {
+                                            // ClrMamePro 文本格式不做高亮，只用等宽字体显示
                                             let mut layouter = |_ui: &egui::Ui, text: &str, _wrap_width: f32| {
-                                                let job = xml_highlight_job(_ui, text);
+                                                let job = match format {
+                                                    DatFormat::Xml => xml_highlight_job(_ui, text),
+                                                    DatFormat::ClrMamePro => egui::text::LayoutJob::simple(
+                                                        text.to_string(),
+                                                        egui::TextStyle::Monospace.resolve(_ui.style()),
+                                                        _ui.visuals().text_color(),
+                                                        f32::INFINITY,
+                                                    ),
+                                                };
                                                 _ui.fonts(|f| f.layout_job(job))
                                             };
                                             ui.add(
@@ -1031,45 +1882,95 @@ impl App for RetroGameManagerApp {
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for (i, g) in results.iter().take(500).enumerate() {
+            if self.table_view {
+                self.results_table(ui, &results);
+                return;
+            }
+            let metrics = card_metrics(ui);
+            let current = self.result_list.as_ref().is_some_and(|list| {
+                list.is_current(&results, self.group_clones, &self.expanded_groups, metrics)
+            });
+            if !current {
+                let groups: Vec<ResultGroup> = if self.group_clones {
+                    self.clone_index.group(&results)
+                } else {
+                    results
+                        .iter()
+                        .map(|&head| ResultGroup {
+                            head,
+                            members: Vec::new(),
+                        })
+                        .collect()
+                };
+                self.result_list = Some(ResultList::build(
+                    &results,
+                    &groups,
+                    self.group_clones,
+                    &self.expanded_groups,
+                    metrics,
+                    |i| card_lines(&self.index[i]),
+                ));
+            }
+            let Some(list) = self.result_list.as_ref() else {
+                return;
+            };
+            let query = &self.parsed_query;
+            let indent = ui.spacing().indent;
+            let mut clicked = None;
+            let mut toggled = None;
+            // 只布局可见区域内的行，其余行按预先算好的高度占位
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show_viewport(ui, |ui, viewport| {
+                    ui.set_height(list.total_height());
+                    let top = ui.max_rect().top();
                     let width = ui.available_width();
-                    let card_width = (width - 12.0).max(0.0);
-                    let inner = egui::Frame::group(ui.style()).show(ui, |ui| {
-                        ui.set_width(card_width);
-                        let tokens = tokenize_query(&self.query);
-                        let job = build_highlight_job(&g.name, &tokens, ui.style());
-                        ui.label(job);
-                        ui.label(format!(
-                            "平台: {} | 区域: {} | 语言: {}",
-                            g.platform,
-                            g.region.as_deref().unwrap_or("未知"),
-                            g.languages.as_deref().unwrap_or("未知")
-                        ));
-                        if let Some(archive_name) = &g.archive_name {
-                            ui.label(format!("归档名: {}", archive_name));
+                    let rows = list.visible(viewport.min.y, viewport.max.y);
+                    ui.skip_ahead_auto_ids(rows.start);
+                    for i in rows {
+                        let (y, height) = list.row_span(i);
+                        let rect = egui::Rect::from_min_size(
+                            egui::pos2(ui.max_rect().left(), top + y),
+                            egui::vec2(width, height),
+                        );
+                        match list.rows[i] {
+                            ResultRow::Card { game, member } => {
+                                let rect = if member {
+                                    rect.with_min_x(rect.left() + indent)
+                                } else {
+                                    rect
+                                };
+                                ui.allocate_ui_at_rect(rect, |ui| {
+                                    let id = ("game_card", game);
+                                    if game_card(ui, &self.index[game], query, id) {
+                                        clicked = Some(game);
+                                    }
+                                });
+                            }
+                            ResultRow::Toggle { head, count } => {
+                                ui.allocate_ui_at_rect(rect, |ui| {
+                                    let expanded = self.expanded_groups.contains(&head);
+                                    let arrow = if expanded { "⏷" } else { "⏵" };
+                                    let label = format!("{} 其他版本 ({})", arrow, count);
+                                    if ui.small_button(label).clicked() {
+                                        toggled = Some(head);
+                                    }
+                                });
+                            }
                         }
-                    });
-                    let rect = inner.response.rect;
-                    let id = egui::Id::new(("game_card", i));
-                    let response = ui.interact(rect, id, egui::Sense::click());
-                    if response.hovered() {
-                        let mut color = ui.visuals().widgets.hovered.bg_fill;
-                        color = color.linear_multiply(0.20);
-                        ui.painter().rect_filled(rect, 4.0, color);
-                    }
-                    if response.clicked() {
-                        self.selected_index = Some(i);
-                        self.show_detail = true;
-                        self.detail_xml_cache = None;
-                        self.detail_tab = DetailTab::Info;
                     }
-                    ui.add_space(4.0);
-                }
-                if results.len() > 500 {
-                    ui.label("结果过多，仅显示前 500 条。请继续缩小搜索条件。");
+                });
+            if let Some(head) = toggled {
+                if !self.expanded_groups.remove(&head) {
+                    self.expanded_groups.insert(head);
                 }
-            });
+            }
+            if let Some(gi) = clicked {
+                self.selected_index = Some(gi);
+                self.show_detail = true;
+                self.detail_source_cache = None;
+                self.detail_tab = DetailTab::Info;
+            }
         });
 
         // 显示首选项配置窗口
@@ -1086,9 +1987,76 @@ impl App for RetroGameManagerApp {
 
                         ui.separator();
 
+                        // 1G1R 优先级：靠前的优先
+                        ui.label("1G1R 地区优先级 (逗号分隔，靠前优先):");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.region_priority)
+                                .hint_text("China, Japan, USA, Europe"),
+                        );
+                        ui.label("1G1R 语言优先级 (逗号分隔，靠前优先):");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.language_priority)
+                                .hint_text("Zh, Ja, En"),
+                        );
+
+                        ui.separator();
+
+                        // DAT 库：启用、名称、目录
+                        ui.label("DAT 库:");
+                        let mut remove = None;
+                        egui::Grid::new("library_grid")
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                for (i, lib) in self.library_draft.iter_mut().enumerate() {
+                                    ui.checkbox(&mut lib.enabled, "");
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut lib.name)
+                                            .desired_width(100.0),
+                                    );
+                                    ui.label(lib.path.display().to_string());
+                                    if ui.button("删除").clicked() {
+                                        remove = Some(i);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        if let Some(i) = remove {
+                            self.library_draft.remove(i);
+                        }
+                        if ui.button("添加目录…").clicked() {
+                            if let Some(dir) = FileDialog::new().pick_folder() {
+                                let library = LibraryRoot::new(&self.library_draft, dir);
+                                // 与已有的库重叠（相同、包含或被包含）时不添加
+                                self.library_error = loader::check_libraries(
+                                    self.library_draft.iter().chain([&library]),
+                                )
+                                .err()
+                                .map(|e| e.to_string());
+                                if self.library_error.is_none() {
+                                    self.library_draft.push(library);
+                                }
+                            }
+                        }
+                        if let Some(err) = &self.library_error {
+                            ui.colored_label(ui.visuals().error_fg_color, err);
+                        }
+
+                        ui.separator();
+
                         if ui.button("保存").clicked() {
+                            match loader::check_libraries(self.library_draft.iter()) {
+                                Ok(()) => {
+                                    self.libraries = self.library_draft.clone();
+                                    self.library_error = None;
+                                }
+                                Err(e) => self.library_error = Some(e.to_string()),
+                            }
                             // 保存配置到recent_store
                             self.persist_recents();
+                            // 启用的库有变化时重新加载索引
+                            if self.enabled_libraries() != self.active_libraries {
+                                self.reload_libraries(ctx);
+                            }
                         }
 
                         if ui.button("取消").clicked() {
@@ -1102,6 +2070,309 @@ impl App for RetroGameManagerApp {
             }
         }
 
+        // 校验结束与窗口是否打开无关；窗口已关闭时在状态栏提示
+        if self.verify_running && self.verify_result.lock().unwrap().is_some() {
+            self.verify_running = false;
+            if !self.show_verify {
+                self.status = "收藏校验完成，可在“校验报告”中查看".to_string();
+            }
+        }
+
+        // 显示收藏校验报告
+        if self.show_verify {
+            let mut open = true;
+            let report = self.verify_result.lock().unwrap().clone();
+            egui::Window::new("收藏校验报告")
+                .open(&mut open)
+                .resizable(true)
+                .default_size(egui::vec2(600.0, 500.0))
+                .show(ctx, |ui| match &report {
+                    None => {
+                        let done = self.verify_progress.done.load(Ordering::Relaxed);
+                        let total = self.verify_progress.total.load(Ordering::Relaxed);
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("正在校验… {}/{}", done, total));
+                        });
+                        ctx.request_repaint_after(std::time::Duration::from_millis(200));
+                    }
+                    Some(report) => {
+                        ui.label(format!("目录: {}", report.root.display()));
+                        ui.label(format!(
+                            "已扫描 {} 个文件，未识别 {} 个，读取失败 {} 个",
+                            report.scanned,
+                            report.unknown.len(),
+                            report.errors.len()
+                        ));
+                        ui.separator();
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            if report.platforms.is_empty() {
+                                ui.label("没有文件与数据库匹配");
+                            }
+                            for p in &report.platforms {
+                                ui.collapsing(
+                                    format!(
+                                        "{} — 已有 {} / 缺失 {}",
+                                        p.platform,
+                                        p.have.len(),
+                                        p.missing.len()
+                                    ),
+                                    |ui| {
+                                        ui.collapsing(format!("已有 ({})", p.have.len()), |ui| {
+                                            for name in &p.have {
+                                                ui.label(name);
+                                            }
+                                        });
+                                        ui.collapsing(
+                                            format!("缺失 ({})", p.missing.len()),
+                                            |ui| {
+                                                for name in &p.missing {
+                                                    ui.label(name);
+                                                }
+                                            },
+                                        );
+                                    },
+                                );
+                            }
+                            if !report.unknown.is_empty() {
+                                ui.collapsing(
+                                    format!("未识别文件 ({})", report.unknown.len()),
+                                    |ui| {
+                                        for path in &report.unknown {
+                                            ui.label(path.display().to_string());
+                                        }
+                                    },
+                                );
+                            }
+                            if !report.errors.is_empty() {
+                                ui.collapsing(
+                                    format!("读取失败 ({})", report.errors.len()),
+                                    |ui| {
+                                        for e in &report.errors {
+                                            ui.label(e);
+                                        }
+                                    },
+                                );
+                            }
+                        });
+                    }
+                });
+            if !open {
+                self.show_verify = false;
+            }
+        }
+
+        // 显示批量重命名预览
+        if self.show_batch_rename {
+            let mut open = true;
+            let mut apply = false;
+            let mut undo = false;
+            egui::Window::new("批量重命名")
+                .open(&mut open)
+                .resizable(true)
+                .default_size(egui::vec2(800.0, 500.0))
+                .show(ctx, |ui| {
+                    let mut guard = self.rename_plan.lock().unwrap();
+                    match guard.as_mut() {
+                        None => {
+                            let done = self.rename_progress.done.load(Ordering::Relaxed);
+                            let total = self.rename_progress.total.load(Ordering::Relaxed);
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(format!("正在匹配文件… {}/{}", done, total));
+                            });
+                            ctx.request_repaint_after(std::time::Duration::from_millis(200));
+                        }
+                        Some(plan) => {
+                            let conflicts =
+                                plan.items.iter().filter(|i| i.conflict.is_some()).count();
+                            ui.label(format!("目录: {}", plan.root.display()));
+                            ui.label(format!(
+                                "待重命名 {} 个，冲突 {} 个，未匹配 {} 个，无需更改 {} 个",
+                                plan.items.len(),
+                                conflicts,
+                                plan.unmatched.len(),
+                                plan.unchanged
+                            ));
+                            ui.separator();
+                            egui::ScrollArea::vertical()
+                                .max_height(ui.available_height() - 40.0)
+                                .show(ui, |ui| {
+                                    egui::Grid::new("rename_plan_grid")
+                                        .striped(true)
+                                        .num_columns(6)
+                                        .show(ui, |ui| {
+                                            ui.strong("");
+                                            ui.strong("原文件名");
+                                            ui.strong("新文件名");
+                                            ui.strong("游戏");
+                                            ui.strong("匹配方式");
+                                            ui.strong("状态");
+                                            ui.end_row();
+                                            for item in plan.items.iter_mut() {
+                                                ui.checkbox(&mut item.enabled, "");
+                                                let file_name = |p: &Path| {
+                                                    p.file_name()
+                                                        .map(|n| n.to_string_lossy().to_string())
+                                                        .unwrap_or_default()
+                                                };
+                                                ui.label(file_name(&item.from));
+                                                ui.label(file_name(&item.to));
+                                                ui.label(&item.game);
+                                                ui.label(match item.kind {
+                                                    MatchKind::Hash => "校验值",
+                                                    MatchKind::Name => "名称",
+                                                });
+                                                match &item.conflict {
+                                                    Some(c) => ui.colored_label(Color32::RED, c),
+                                                    None => ui.label("正常"),
+                                                };
+                                                ui.end_row();
+                                            }
+                                        });
+                                    if !plan.unmatched.is_empty() {
+                                        ui.collapsing(
+                                            format!("未匹配文件 ({})", plan.unmatched.len()),
+                                            |ui| {
+                                                for p in &plan.unmatched {
+                                                    ui.label(p.display().to_string());
+                                                }
+                                            },
+                                        );
+                                    }
+                                });
+                        }
+                    }
+                    let ready = guard.is_some();
+                    drop(guard);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(ready, egui::Button::new("应用")).clicked() {
+                            apply = true;
+                        }
+                        if ui.button("撤销上次批量重命名").clicked() {
+                            undo = true;
+                        }
+                    });
+                });
+            if apply {
+                if let Some(plan) = self.rename_plan.lock().unwrap().take() {
+                    self.status = rename_status("批量重命名完成", &rename::apply_plan(&plan));
+                }
+                open = false;
+            }
+            if undo {
+                self.status = match rename::undo_last() {
+                    Ok(outcome) => rename_status("已撤销批量重命名", &outcome),
+                    Err(e) => format!("撤销失败: {:#}", e),
+                };
+            }
+            if !open {
+                self.show_batch_rename = false;
+            }
+        }
+
+        // 显示解析失败的 DAT 列表
+        if self.show_diagnostics {
+            let mut open = true;
+            egui::Window::new("诊断")
+                .open(&mut open)
+                .resizable(true)
+                .default_size(egui::vec2(600.0, 400.0))
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "{} 个文件解析失败，未加入索引",
+                        self.parse_failures.len()
+                    ));
+                    ui.separator();
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        for failure in &self.parse_failures {
+                            ui.strong(&failure.file_path);
+                            if let Some(pos) = failure.position {
+                                ui.label(format!("字节位置: {}", pos));
+                            }
+                            for (depth, msg) in failure.chain.iter().enumerate() {
+                                let prefix = if depth == 0 { "" } else { "↳ " };
+                                ui.monospace(format!("{}{}", prefix, msg));
+                            }
+                            ui.separator();
+                        }
+                    });
+                });
+            if !open {
+                self.show_diagnostics = false;
+            }
+        }
+
+        // 已加载的 DAT 列表，用于确认当前使用的版本
+        if self.show_dat_files {
+            let mut open = true;
+            egui::Window::new("DAT 文件")
+                .open(&mut open)
+                .resizable(true)
+                .default_size(egui::vec2(800.0, 450.0))
+                .show(ctx, |ui| {
+                    let mut dats: Vec<&DatInfo> = self.dat_files.iter().collect();
+                    dats.sort_by(|a, b| {
+                        (&a.library, &a.header.name, &a.file_path).cmp(&(
+                            &b.library,
+                            &b.header.name,
+                            &b.file_path,
+                        ))
+                    });
+                    ui.label(format!(
+                        "已加载 {} 个 DAT，共 {} 个游戏条目",
+                        dats.len(),
+                        dats.iter().map(|d| d.game_count).sum::<usize>()
+                    ));
+                    ui.separator();
+                    egui::ScrollArea::both().show(ui, |ui| {
+                        egui::Grid::new("dat_files_grid")
+                            .striped(true)
+                            .num_columns(6)
+                            .show(ui, |ui| {
+                                ui.strong("名称");
+                                ui.strong("版本");
+                                ui.strong("日期");
+                                ui.strong("游戏数");
+                                ui.strong("来源库");
+                                ui.strong("文件");
+                                ui.end_row();
+                                for dat in dats {
+                                    let header = &dat.header;
+                                    // 描述、作者、主页放在悬停提示中
+                                    let details: Vec<String> = [
+                                        ("描述", &header.description),
+                                        ("作者", &header.author),
+                                        ("主页", &header.homepage),
+                                    ]
+                                    .iter()
+                                    .filter_map(|(k, v)| {
+                                        v.as_ref().map(|v| format!("{}: {}", k, v))
+                                    })
+                                    .collect();
+                                    let name = ui.label(header.name.as_deref().unwrap_or("-"));
+                                    if !details.is_empty() {
+                                        name.on_hover_text(details.join("\n"));
+                                    }
+                                    ui.label(header.version.as_deref().unwrap_or("-"));
+                                    ui.label(header.date.as_deref().unwrap_or("-"));
+                                    ui.label(dat.game_count.to_string());
+                                    ui.label(&dat.library);
+                                    match &dat.member {
+                                        Some(m) => ui.label(format!("{} ({})", dat.file_path, m)),
+                                        None => ui.label(&dat.file_path),
+                                    };
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+            if !open {
+                self.show_dat_files = false;
+            }
+        }
+
         // 显示关于窗口
         if self.show_about {
             let mut open = true;
@@ -1132,111 +2403,162 @@ impl App for RetroGameManagerApp {
 
         // 处理文件重命名
         if let Some((file_path, game)) = self.pending_file_rename.take() {
-            if let Err(e) = self.rename_file_to_game_name(&file_path, &game) {
-                // 显示错误消息（在实际应用中可能需要更好的错误处理）
-                eprintln!("重命名文件失败: {}", e);
-            }
+            self.status = format!("正在重命名并校验 {}…", file_path.display());
+            let result = Arc::clone(&self.file_rename_result);
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let outcome = rename_file_to_game_name(&file_path, game);
+                *result.lock().unwrap() = Some(outcome);
+                ctx.request_repaint();
+            });
         }
-    }
-}
-
-type LoadIndexResult = (
-    Vec<GameEntry>,
-    Vec<String>,
-    Vec<String>,
-    Vec<String>,
-    String,
-);
-
-fn load_index(xmldb_dir: &Path) -> Result<LoadIndexResult> {
-    if !xmldb_dir.exists() {
-        return Err(anyhow!("xmldb 目录不存在: {}", xmldb_dir.display()));
-    }
-
-    let mut files: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(xmldb_dir).into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() {
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                if ext == "xml" {
-                    files.push(path.to_path_buf());
+        let finished = self.file_rename_result.lock().unwrap().take();
+        if let Some(outcome) = finished {
+            match &outcome.renamed {
+                Ok(new_filename) => {
+                    let check = self.check_hashes_against_game(&outcome);
+                    self.status = format!("已重命名为 {}{}", new_filename, check);
+                }
+                Err(e) => {
+                    // 显示错误消息（在实际应用中可能需要更好的错误处理）
+                    eprintln!("重命名文件失败: {}", e);
+                    self.status = format!("重命名文件失败: {}", e);
                 }
             }
         }
     }
+}
 
-    if files.is_empty() {
-        return Ok((
-            Vec::new(),
-            Vec::new(),
-            Vec::new(),
-            Vec::new(),
-            "未找到 XML 文件".to_string(),
-        ));
+// 批量重命名/撤销结果的状态栏文字；撤销记录保存失败时单独警告
+fn rename_status(action: &str, outcome: &rename::RenameOutcome) -> String {
+    let mut text = format!("{}：{} 个文件", action, outcome.count);
+    if let Some(first) = outcome.errors.first() {
+        text.push_str(&format!("，{} 个失败（{}）", outcome.errors.len(), first));
     }
+    if let Some(e) = &outcome.journal_error {
+        text.push_str(&format!("。警告：无法保存撤销记录（{}）", e));
+    }
+    text
+}
 
-    let games: Vec<GameEntry> = files
-        .par_iter()
-        .filter_map(|p| parse_games_from_file(p).ok())
-        .flatten()
-        .collect();
-
-    let mut platforms: Vec<String> = games.iter().map(|g| g.platform.clone()).collect();
-    platforms.sort_unstable();
-    platforms.dedup();
-
-    let mut regions: Vec<String> = games
+// 标记的显示名称，逗号分隔
+fn flag_labels(flags: &[TagFlag]) -> String {
+    flags
         .iter()
-        .filter_map(|g| g.region.as_ref().map(|s| s.trim().to_string()))
-        .filter(|s| !s.is_empty())
-        .collect();
-    regions.sort_unstable();
-    regions.dedup();
+        .map(|f| f.label())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    let mut languages: Vec<String> = games
-        .iter()
-        .filter_map(|g| g.languages.as_ref())
-        .flat_map(|s| s.split(','))
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-    languages.sort_unstable();
-    languages.dedup();
+// 卡片显示的文字行数，需与 game_card 一致，用于虚拟化列表预先计算行高
+fn card_lines(g: &GameEntry) -> usize {
+    2 + usize::from(g.archive_name.is_some())
+        + usize::from(g.description.as_ref().is_some_and(|d| *d != g.name))
+        + usize::from(g.year.is_some())
+        + usize::from(g.clone_of.is_some())
+        + usize::from(g.driver_status.is_some())
+        + usize::from(!g.tags.flags.is_empty())
+}
 
-    let status = format!(
-        "已索引平台 {} 个，游戏条目 {} 条",
-        platforms.len(),
-        games.len()
-    );
+// 虚拟化列表计算卡片行高用到的尺寸
+fn card_metrics(ui: &egui::Ui) -> RowMetrics {
+    let line = ui.text_style_height(&egui::TextStyle::Body);
+    let spacing = ui.spacing().item_spacing.y;
+    let frame = egui::Frame::group(ui.style());
+    RowMetrics {
+        line,
+        spacing,
+        // 边框内边距，加上卡片后的控件间距和 4 像素空白
+        card_padding: frame.inner_margin.sum().y + spacing + 4.0,
+        toggle: line + spacing + 4.0,
+    }
+}
 
-    Ok((games, platforms, regions, languages, status))
+// 结果卡片，返回是否被点击；每项信息占一行，过长时截断，保证高度与 card_lines 一致
+fn game_card(ui: &mut egui::Ui, g: &GameEntry, query: &Query, id: impl std::hash::Hash) -> bool {
+    let width = ui.available_width();
+    let card_width = (width - 12.0).max(0.0);
+    let line = |ui: &mut egui::Ui, text: String| {
+        ui.add(egui::Label::new(text).truncate(true));
+    };
+    let inner = egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(card_width);
+        let spans = query.highlight_spans(&g.name);
+        let job = build_highlight_job(&g.name, &spans, ui.style());
+        ui.add(egui::Label::new(job).truncate(true));
+        line(
+            ui,
+            format!(
+                "平台: {} | 区域: {} | 语言: {}",
+                g.platform,
+                g.region.as_deref().unwrap_or("未知"),
+                g.languages.as_deref().unwrap_or("未知")
+            ),
+        );
+        if let Some(archive_name) = &g.archive_name {
+            line(ui, format!("归档名: {}", archive_name));
+        }
+        // Logiqx DAT 的描述通常是完整标题，与 name 相同时不再重复显示
+        if let Some(desc) = g.description.as_ref().filter(|d| **d != g.name) {
+            line(ui, format!("描述: {}", desc));
+        }
+        if let Some(year) = &g.year {
+            line(ui, format!("年份: {}", year));
+        }
+        if let Some(clone_of) = &g.clone_of {
+            line(ui, format!("父版本: {}", clone_of));
+        }
+        if let Some(status) = &g.driver_status {
+            line(ui, format!("驱动状态: {}", status));
+        }
+        if !g.tags.flags.is_empty() {
+            line(ui, format!("标记: {}", flag_labels(&g.tags.flags)));
+        }
+    });
+    let rect = inner.response.rect;
+    let response = ui.interact(rect, egui::Id::new(id), egui::Sense::click());
+    if response.hovered() {
+        let mut color = ui.visuals().widgets.hovered.bg_fill;
+        color = color.linear_multiply(0.20);
+        ui.painter().rect_filled(rect, 4.0, color);
+    }
+    ui.add_space(4.0);
+    response.clicked()
 }
 
-fn filter_results<'a>(
-    index: &'a [GameEntry],
-    query: &str,
+// 返回匹配条目在 index 中的下标
+#[allow(clippy::too_many_arguments)]
+fn filter_results(
+    index: &[GameEntry],
+    search: &SearchIndex,
+    query: &Query,
     platforms: &[String], // 支持多选
     region: &str,
     language: &str,
-) -> Vec<&'a GameEntry> {
-    let q = query.trim().to_lowercase();
+    library: &str,
+    all_fields: bool,
+) -> Vec<usize> {
     let r = region.trim().to_lowercase();
     let l = language.trim().to_lowercase();
 
     // 创建平台过滤器的HashSet以提高查找效率
     let platform_set: std::collections::HashSet<&String> = platforms.iter().collect();
 
-    index
-        .iter()
-        .filter(|g| {
+    // 先用倒排索引取出候选，再逐条检查
+    let candidates = search.candidates(
+        index.len(),
+        query,
+        platforms,
+        region,
+        language,
+        library,
+        all_fields,
+    );
+    let mut scored: Vec<(usize, f32)> = candidates
+        .into_par_iter()
+        .map(|i| (i, &index[i]))
+        .filter(|(_, g)| {
             let mut ok = true;
-            if !q.is_empty() {
-                ok &= g.name.to_lowercase().contains(&q)
-                    || g.archive_name
-                        .as_deref()
-                        .map(|n| n.to_lowercase().contains(&q))
-                        .unwrap_or(false);
-            }
             // 平台：支持多选（使用HashSet提高效率）
             if !platforms.is_empty() {
                 ok &= platform_set.contains(&g.platform);
@@ -1261,15 +2583,52 @@ fn filter_results<'a>(
                     })
                     .unwrap_or(false);
             }
+            // 来源库：精确匹配
+            if !library.is_empty() {
+                ok &= g.library == library;
+            }
             ok
         })
-        .take(1000) // 限制结果数量以避免卡顿
-        .collect()
+        // 关键字最后匹配，先用便宜的条件排除
+        .filter_map(|(i, g)| query.score(g, all_fields).map(|score| (i, score)))
+        .collect();
+    // 按相关度从高到低排列，得分相同的保持索引顺序
+    if !query.is_empty() {
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    }
+    scored.into_iter().map(|(i, _)| i).collect()
 }
 
 impl RetroGameManagerApp {
-    // 重命名文件为游戏名称
-    fn rename_file_to_game_name(&self, file_path: &Path, game: &GameEntry) -> Result<()> {
+    // 根据后台算出的校验值核对文件是否属于该游戏，不符时在索引中查找疑似的条目
+    fn check_hashes_against_game(&self, outcome: &FileRenameOutcome) -> String {
+        let game = &outcome.game;
+        let hashes = match &outcome.hashes {
+            None => return String::new(),
+            Some(Ok(h)) => h,
+            Some(Err(e)) => return format!("（无法校验: {}）", e),
+        };
+        let matches_game =
+            |h: &verify::FileHashes| game.roms.iter().any(|r| verify::rom_matches(r, h));
+        if hashes.iter().any(matches_game) {
+            return "（校验通过）".to_string();
+        }
+        let other = self.index.iter().find(|g| {
+            g.roms
+                .iter()
+                .any(|r| hashes.iter().any(|h| verify::rom_matches(r, h)))
+        });
+        match other {
+            Some(g) => format!("（警告：文件内容与所选游戏不符，疑似 {}）", g.name),
+            None => "（警告：文件校验值与所选游戏不符）".to_string(),
+        }
+    }
+}
+
+// 重命名文件为游戏名称，再计算新文件的校验值（zip 文件读取成员的 CRC32）。
+// 在后台线程中调用
+fn rename_file_to_game_name(file_path: &Path, game: GameEntry) -> FileRenameOutcome {
+    let renamed = (|| {
         // 获取文件的父目录
         let parent_dir = file_path
             .parent()
@@ -1291,19 +2650,82 @@ impl RetroGameManagerApp {
             file_path.display(),
             new_path.display()
         ))?;
+        Ok((new_filename, new_path))
+    })();
+    let hashes = match &renamed {
+        Ok((_, new_path)) if !game.roms.is_empty() => {
+            let matches_game =
+                |h: &verify::FileHashes| game.roms.iter().any(|r| verify::rom_matches(r, h));
+            Some(verify::identify_file(new_path, matches_game))
+        }
+        _ => None,
+    };
+    FileRenameOutcome {
+        renamed: renamed.map(|(name, _)| name),
+        hashes,
+        game,
+    }
+}
 
-        Ok(())
+// 读取多个条目的原始片段并按结果顺序拼接，同一个 DAT 只打开一次
+fn collect_sources(index: &[GameEntry], ids: &[usize]) -> Result<String> {
+    let mut by_file: BTreeMap<(&str, Option<&str>), Vec<usize>> = BTreeMap::new();
+    for &gi in ids {
+        let g = &index[gi];
+        by_file
+            .entry((g.file_path.as_str(), g.dat_member.as_deref()))
+            .or_default()
+            .push(gi);
+    }
+    let mut texts: HashMap<usize, String> = HashMap::new();
+    for ((file, member), gis) in by_file {
+        let spans: Vec<_> = gis
+            .iter()
+            .map(|&gi| index[gi].source_span.clone())
+            .collect();
+        let sources = dat::read_sources(Path::new(file), member, &spans)?;
+        texts.extend(gis.into_iter().zip(sources));
     }
+    Ok(ids
+        .iter()
+        .filter_map(|gi| texts.remove(gi))
+        .collect::<Vec<_>>()
+        .join("\n"))
 }
 
-// 清理文件名，移除非法字符
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .map(|c| match c {
-            '<' | '>' | ':' | '\"' | '/' | '\\' | '|' | '?' | '*' => '_',
-            _ => c,
-        })
-        .collect()
+// 把结果导出为 Logiqx DAT，所有结果同属一个平台时以平台名作为 DAT 名称
+fn export_results(
+    index: &[GameEntry],
+    ids: &[usize],
+    path: &Path,
+    one_game_one_rom: bool,
+) -> Result<()> {
+    let games: Vec<&GameEntry> = ids.iter().map(|&i| &index[i]).collect();
+    let platforms: HashSet<&str> = games.iter().map(|g| g.platform.as_str()).collect();
+    let mut name = match platforms.iter().next() {
+        Some(p) if platforms.len() == 1 => p.to_string(),
+        _ => "自选合集".to_string(),
+    };
+    if one_game_one_rom {
+        name.push_str(" (1G1R)");
+    }
+    let header = DatHeader {
+        name: Some(name),
+        description: Some(format!("共 {} 个条目", games.len())),
+        author: Some("retro-game-manager".to_string()),
+        ..DatHeader::default()
+    };
+    dat::write_logiqx(path, &header, &games)
+}
+
+// 合并到已排序去重的列表中
+fn merge_sorted(list: &mut Vec<String>, new: Vec<String>) {
+    if new.iter().all(|v| list.binary_search(v).is_ok()) {
+        return;
+    }
+    list.extend(new);
+    list.sort_unstable();
+    list.dedup();
 }
 
 fn add_recent(list: &mut Vec<String>, value: &str) {
@@ -1428,7 +2850,7 @@ fn main() -> Result<(), Error> {
         Box::new(|cc| {
             // 尝试设置原生菜单（如果API可用）
             // 这需要检查egui版本是否支持此功能
-            Box::new(RetroGameManagerApp::new(cc).unwrap())
+            Box::new(RetroGameManagerApp::new(cc))
         }),
     )
 }
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use unicode_normalization::UnicodeNormalization;
+
+// 搜索用的文本归一化：全角/半角、繁简、片假名/平假名统一，
+// 以及中日文名称的拼音/罗马字，便于用拉丁字母搜索
+
+// 繁体 -> 简体，收录游戏名称中常见的字
+const TRADITIONAL_PAIRS: &str = "\
+遊游 戲戏 夢梦 龍龙 傳传 說说 戰战 國国 無无 雙双 機机 動动 電电 華华 鬥斗 惡恶 門门 們们 來来 時时 \
+實实 寶宝 貓猫 魚鱼 鳥鸟 馬马 車车 東东 樂乐 語语 學学 書书 體体 點点 發发 開开 關关 長长 風风 雲云 \
+飛飞 靈灵 術术 劍剑 俠侠 傑杰 將将 軍军 團团 隊队 擊击 殺杀 槍枪 彈弹 艦舰 蟲虫 獸兽 騎骑 劇剧 場场 \
+會会 歲岁 記记 錄录 紀纪 廳厅 聖圣 護护 衛卫 險险 島岛 陸陆 進进 殲歼 滅灭 鐵铁 鋼钢 銀银 錢钱 鏡镜 \
+鐘钟 鍵键 盤盘 從从 眾众 衆众 羅罗 蘭兰 義义 愛爱 戀恋 親亲 媽妈 爺爷 兒儿 孫孙 號号 塊块 壞坏 壓压 \
+隻只 麼么 個个 為为 與与 萬万 億亿 單单 廣广 麵面 鄉乡 莊庄 園园 圖图 層层 寫写 讀读 誰谁 請请 話话 \
+詩诗 誌志 認认 識识 讓让 變变 鮮鲜 鯨鲸 鷹鹰 鳳凤 鶴鹤 雞鸡 鴨鸭 豬猪 貝贝 負负 賽赛 購购 買买 賣卖 \
+費费 資资 貨货 質质 價价 網网 線线 紅红 綠绿 藍蓝 經经 結结 給给 終终 練练 組组 絕绝 統统 續续 總总 \
+緣缘 約约 級级 細细 紙纸 純纯 織织 繪绘 歡欢 觀观 見见 視视 覺觉 現现 環环 讚赞 貴贵 陽阳 陰阴 隱隐 \
+際际 離离 難难 雜杂 頭头 題题 顏颜 願愿 類类 顯显 頁页 順顺 領领 飯饭 館馆 驚惊 騰腾 驗验 髮发 麗丽 \
+黃黄 齊齐 齒齿 龜龟 憶忆 撲扑 據据 擴扩 攝摄 敵敌 數数 斷断 於于 曉晓 曆历 歷历 殘残 氣气 沒没 涼凉 \
+淚泪 渾浑 湯汤 滿满 漢汉 潛潜 潔洁 濕湿 災灾 爐炉 爭争 牆墙 狀状 獨独 獵猎 獻献 瑪玛 產产 畫画 當当 \
+療疗 盜盗 盡尽 確确 礦矿 禮礼 禦御 種种 稱称 穩稳 窮穷 競竞 筆笔 節节 範范 築筑 簡简 糧粮 罰罚 習习 \
+聲声 聯联 聽听 腦脑 膽胆 臉脸 興兴 舊旧 藝艺 蘇苏 處处 蝦虾 蠻蛮 衝冲 補补 裝装 製制 複复 襲袭 規规 \
+覽览 訊讯 計计 訓训 設设 許许 評评 試试 詳详 誤误 課课 調调 談谈 論论 諸诸 謎谜 講讲 謝谢 證证 譯译 \
+議议 豐丰 貪贪 賢贤 趕赶 跡迹 踐践 躍跃 軟软 輕轻 載载 輪轮 輸输 轉转 辦办 農农 迴回 運运 過过 達达 \
+違违 遠远 適适 選选 遺遗 邊边 郵邮 醫医 釋释 針针 鈴铃 銃铳 鋒锋 錯错 鍊炼 鎖锁 鎮镇 鏈链 閃闪 閉闭 \
+問问 間间 閣阁 闖闯 陣阵 隨随 雖虽 靜静 韓韩 頂顶 預预 頻频 颱台 飄飘 養养 餘余 馴驯 駕驾 驅驱 鬧闹 \
+魯鲁 鳴鸣 鴻鸿 麥麦 黨党 龐庞 廢废 彎弯 強强 後后 徑径 徵征 恆恒 悅悦 惱恼 慘惨 慣惯 態态 憂忧 應应 \
+懷怀 懸悬 戶户 掃扫 掛挂 採采 換换 揚扬 搖摇 擔担 擺摆 擾扰 攜携 敗败 斬斩 晝昼 暫暂 條条 棄弃 極极 \
+榮荣 構构 樓楼 標标 樣样 橋桥 檢检 權权 歐欧 歸归 殼壳 毀毁 決决 況况 溫温 測测 漁渔 滾滚 濟济 燈灯 \
+燒烧 營营 燦灿 爛烂 犧牺 獅狮 畢毕 異异 瘋疯 監监 碼码 禪禅 稅税 窩窝 籃篮 籠笼 紛纷 紋纹 絲丝 維维 \
+緊紧 編编 縣县 縱纵 繩绳 繼继 罵骂 聞闻 脫脱 腳脚 臨临 臺台 葉叶 蓋盖 藥药 蘋苹 蘿萝 虛虚 螢萤 裏里 \
+裡里 褲裤 觸触 誕诞 諾诺 謀谋 譜谱 賊贼 賓宾 賞赏 賭赌 贏赢 趙赵 蹤踪 軌轨 輔辅 輝辉 轟轰 辭辞 邏逻 \
+鄰邻 醜丑 銅铜 鋪铺 錦锦 鍋锅 鎧铠 鑰钥 閱阅 陳陈 霧雾 韻韵 響响 頓顿 顆颗 飢饥 飲饮 飽饱 餓饿 騙骗 \
+驢驴 鬱郁 鯊鲨 鱷鳄 鴉鸦 鵝鹅 鵬鹏 齡龄";
+
+fn simplified_table() -> &'static HashMap<char, char> {
+    static TABLE: OnceLock<HashMap<char, char>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        TRADITIONAL_PAIRS
+            .split_whitespace()
+            .filter_map(|pair| {
+                let mut chars = pair.chars();
+                Some((chars.next()?, chars.next()?))
+            })
+            .collect()
+    })
+}
+
+// 片假名转为对应的平假名
+fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_ideograph(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}')
+}
+
+// 比较用的形式：全角/半角统一（NFKC）、小写、繁体转简体、片假名转平假名
+pub fn fold(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    let table = simplified_table();
+    text.nfkc()
+        .flat_map(char::to_lowercase)
+        .map(|c| to_hiragana(table.get(&c).copied().unwrap_or(c)))
+        .collect()
+}
+
+// 平假名的平文式（Hepburn）罗马字，拗音、促音和小写假名在 kana_romaji 中组合
+fn hepburn(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' | 'ゐ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' | 'ゑ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ん' => "n",
+        'ゔ' => "vu",
+        _ => return None,
+    })
+}
+
+// 假名连写转为罗马字：拗音（きゃ -> kya、しゃ -> sha）、外来语的小写元音
+// （ふぁ -> fa、てぃ -> ti）、促音（っ 重复下一个辅音，っち -> tchi），长音符号省略
+fn kana_romaji(kana: &[char]) -> Vec<String> {
+    let mut syllables: Vec<String> = Vec::new();
+    let mut double = false;
+    let mut i = 0;
+    while i < kana.len() {
+        let c = to_hiragana(kana[i]);
+        i += 1;
+        if c == 'っ' {
+            double = true;
+            continue;
+        }
+        let Some(base) = hepburn(c) else {
+            continue;
+        };
+        let mut syllable = base.to_string();
+        let next = kana.get(i).map(|&n| to_hiragana(n));
+        match next {
+            // 拗音：い段假名 + 小写 ゃゅょ
+            Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if base.len() > 1 && base.ends_with('i') => {
+                let stem = &base[..base.len() - 1];
+                let vowel = &hepburn(small).unwrap_or_default()[1..];
+                syllable = if matches!(stem, "sh" | "ch" | "j") {
+                    format!("{}{}", stem, vowel)
+                } else {
+                    format!("{}y{}", stem, vowel)
+                };
+                i += 1;
+            }
+            // 外来语：假名 + 小写元音，う -> w、ゔ -> v
+            Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) if c != 'ぁ' => {
+                let stem = base.trim_end_matches(['a', 'i', 'u', 'e', 'o']);
+                let stem = if stem.is_empty() { "w" } else { stem };
+                syllable = format!("{}{}", stem, hepburn(small).unwrap_or_default());
+                i += 1;
+            }
+            _ => {}
+        }
+        if std::mem::take(&mut double) {
+            if syllable.starts_with("ch") {
+                syllable.insert(0, 't');
+            } else if let Some(first) = syllable.chars().next().filter(|f| !"aiueon".contains(*f)) {
+                syllable.insert(0, first);
+            }
+        }
+        syllables.push(syllable);
+    }
+    syllables
+}
+
+// 中日文名称的拉丁字母形式
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Romanized {
+    // 全拼/罗马字，不含空格，如 "chaojimaliaoxiongdi"、"doragonkuesuto"
+    pub full: String,
+    // 汉字只取拼音首字母，假名和字母数字原样保留，如 "cjmlaxd"
+    pub initials: String,
+}
+
+// 名称中没有汉字或假名时返回 None。
+// 汉字一律按普通话读音转写，日文汉字的训读无法还原
+pub fn romanize(text: &str) -> Option<Romanized> {
+    if !text.chars().any(|c| is_ideograph(c) || is_kana(c)) {
+        return None;
+    }
+    let table = simplified_table();
+    let mut out = Romanized::default();
+    let chars: Vec<char> = text.nfkc().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = table.get(&chars[i]).copied().unwrap_or(chars[i]);
+        if is_kana(c) {
+            // 连续的假名一起转写，以便处理拗音和促音
+            let start = i;
+            while chars.get(i).copied().is_some_and(is_kana) {
+                i += 1;
+            }
+            for syllable in kana_romaji(&chars[start..i]) {
+                out.full.push_str(&syllable);
+                out.initials.push_str(&syllable);
+            }
+            continue;
+        }
+        i += 1;
+        if is_ideograph(c) {
+            let Some(pinyin) = deunicode::deunicode_char(c) else {
+                continue;
+            };
+            let pinyin = pinyin.trim().to_ascii_lowercase();
+            out.full.push_str(&pinyin);
+            out.initials.extend(pinyin.chars().next());
+        } else if c.is_ascii_alphanumeric() {
+            out.full.push(c.to_ascii_lowercase());
+            out.initials.push(c.to_ascii_lowercase());
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(text: &str) -> String {
+        romanize(text).unwrap().full
+    }
+
+    #[test]
+    fn romanizes_kana_as_hepburn() {
+        assert_eq!(full("ファイナルファンタジー"), "fainarufantaji");
+        assert!(full("キャプテン翼").starts_with("kyaputen"));
+        assert_eq!(full("ポケットモンスター"), "pokettomonsuta");
+    }
+
+    #[test]
+    fn romanizes_yoon_and_sokuon() {
+        assert_eq!(full("しゃちょう"), "shachou");
+        assert_eq!(full("マッチ"), "matchi");
+        assert_eq!(full("ドラゴンクエスト"), "doragonkuesuto");
+        assert_eq!(full("ティーヴァ"), "tiva");
+    }
+}
@@ -0,0 +1,205 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::clones::CloneIndex;
+use crate::tags::{base_title, NameTags, TagFlag};
+use crate::xml::GameEntry;
+
+// 1G1R（一个游戏只保留一个 ROM）：同一父版本组或同名的一组条目中，
+// 优先正式发行的版本，再按地区、语言优先级和修订号挑出最合适的一个
+
+// 地区和语言优先级，列表中靠前的优先，不在列表中的排在最后
+#[derive(Debug, Clone, Default)]
+pub struct Priority {
+    regions: Vec<String>,
+    languages: Vec<String>,
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split([',', '>'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+impl Priority {
+    // 由设置中的文本构造，逗号或 ">" 分隔，如 "China > Japan > USA > Europe"
+    pub fn parse(regions: &str, languages: &str) -> Self {
+        Self {
+            regions: split_list(regions),
+            languages: split_list(languages),
+        }
+    }
+
+    fn rank(list: &[String], values: &[&str]) -> usize {
+        values
+            .iter()
+            .filter_map(|v| list.iter().position(|p| p.eq_ignore_ascii_case(v)))
+            .min()
+            .unwrap_or(list.len())
+    }
+
+    // 排序键，越小越好：发行状态、地区、语言，再按修订号从新到旧
+    fn key(&self, g: &GameEntry) -> (usize, usize, usize, Reverse<Vec<u32>>) {
+        (
+            release_rank(&g.tags),
+            Self::rank(&self.regions, &field_values(&g.region)),
+            Self::rank(&self.languages, &field_values(&g.languages)),
+            Reverse(g.tags.revision_key()),
+        )
+    }
+}
+
+// 正式版最优，其次是非授权的发行版，再次是测试版、原型和演示版，
+// 盗版、修改版和损坏的 dump 排在最后
+fn release_rank(tags: &NameTags) -> usize {
+    if tags.has_any(&[TagFlag::Pirate, TagFlag::Hack, TagFlag::BadDump]) {
+        3
+    } else if tags.has_any(&[TagFlag::Beta, TagFlag::Proto, TagFlag::Demo]) {
+        2
+    } else if tags.has_any(&[TagFlag::Unlicensed]) {
+        1
+    } else {
+        0
+    }
+}
+
+// 字段值，逗号分隔（如 "USA, Europe"、"En,Fr"）
+fn field_values(field: &Option<String>) -> Vec<&str> {
+    field
+        .iter()
+        .flat_map(|s| s.split(','))
+        .map(str::trim)
+        .collect()
+}
+
+// 在结果中每组只保留最优的条目，组的顺序取组内第一个结果的位置。
+// 有父版本关系的按父版本分组，否则按平台和去掉标签后的标题分组
+pub fn select(
+    index: &[GameEntry],
+    results: &[usize],
+    clones: &CloneIndex,
+    priority: &Priority,
+) -> Vec<usize> {
+    let mut best: Vec<usize> = Vec::new();
+    let mut slots: HashMap<(&str, String), usize> = HashMap::new();
+    for &i in results {
+        let root = &index[clones.root_of(i)];
+        let title = base_title(root.description.as_deref().unwrap_or(&root.name));
+        match slots.get(&(root.platform.as_str(), title.clone())) {
+            Some(&s) => {
+                if priority.key(&index[i]) < priority.key(&index[best[s]]) {
+                    best[s] = i;
+                }
+            }
+            None => {
+                slots.insert((root.platform.as_str(), title), best.len());
+                best.push(i);
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::dat::DatSource;
+
+    // 按 dat 模块加载时的方式由名称标签补上地区和语言
+    fn parse(xml: &str) -> Vec<GameEntry> {
+        let source = DatSource {
+            path: Path::new("Test - Platform.xml"),
+            member: None,
+        };
+        let (_, mut games) = crate::xml::parse_games(xml.as_bytes(), &source).unwrap();
+        for g in games.iter_mut() {
+            g.tags = NameTags::parse(&g.name);
+            if !g.tags.regions.is_empty() {
+                g.region = Some(g.tags.regions.join(", "));
+            }
+            if !g.tags.languages.is_empty() {
+                g.languages = Some(g.tags.languages.join(","));
+            }
+        }
+        games
+    }
+
+    fn pick(games: &[GameEntry], results: &[usize], priority: &Priority) -> Vec<String> {
+        let clones = CloneIndex::build(games);
+        select(games, results, &clones, priority)
+            .into_iter()
+            .map(|i| games[i].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn parses_priority_lists() {
+        let p = Priority::parse("China > Japan, USA ,, Europe", " Zh,Ja > En ");
+        assert_eq!(p.regions, ["China", "Japan", "USA", "Europe"]);
+        assert_eq!(p.languages, ["Zh", "Ja", "En"]);
+        assert!(Priority::parse("", " , ").regions.is_empty());
+    }
+
+    #[test]
+    fn groups_by_clone_root_and_prefers_region() {
+        // 日版标题不同，只能通过 cloneof 归入同一组
+        let games = parse(
+            r#"<datafile>
+                <game name="Pocket Monsters Aka (Japan)" cloneof="Pokemon - Red Version (USA, Europe)"></game>
+                <game name="Pokemon - Red Version (USA, Europe)"></game>
+                <game name="Tetris (Japan) (En)"></game>
+            </datafile>"#,
+        );
+        let results = [0, 1, 2];
+        let usa = Priority::parse("USA, Japan", "");
+        assert_eq!(
+            pick(&games, &results, &usa),
+            ["Pokemon - Red Version (USA, Europe)", "Tetris (Japan) (En)"]
+        );
+        let japan = Priority::parse("Japan, USA", "");
+        assert_eq!(
+            pick(&games, &results, &japan),
+            ["Pocket Monsters Aka (Japan)", "Tetris (Japan) (En)"]
+        );
+    }
+
+    #[test]
+    fn groups_by_base_title_and_prefers_releases() {
+        // 没有 cloneof 时按平台和去掉标签后的标题分组
+        let games = parse(
+            r#"<datafile>
+                <game name="Sonic (USA) (Beta)"></game>
+                <game name="Sonic (USA) (Proto)"></game>
+                <game name="Sonic (Europe)"></game>
+                <game name="Sonic (USA) [b]"></game>
+                <game name="Sonic (USA)"></game>
+                <game name="Sonic (USA) (Rev 1)"></game>
+                <game name="Zelda (USA) (Demo)"></game>
+            </datafile>"#,
+        );
+        let priority = Priority::parse("USA, Europe", "");
+        assert_eq!(
+            pick(&games, &[0, 1, 2, 3, 4, 5, 6], &priority),
+            ["Sonic (USA) (Rev 1)", "Zelda (USA) (Demo)"]
+        );
+        // 组里没有美版正式版时，其他地区的正式版优先于美版测试版
+        assert_eq!(pick(&games, &[0, 1, 2, 3], &priority), ["Sonic (Europe)"]);
+        // 只剩开发中的版本时照常按地区和顺序挑选
+        assert_eq!(pick(&games, &[1, 0], &priority), ["Sonic (USA) (Proto)"]);
+    }
+
+    #[test]
+    fn unlicensed_ranks_after_licensed_releases() {
+        let games = parse(
+            r#"<datafile>
+                <game name="Tetris (USA) (Unl)"></game>
+                <game name="Tetris (Japan)"></game>
+            </datafile>"#,
+        );
+        let priority = Priority::parse("USA, Japan", "");
+        assert_eq!(pick(&games, &[0, 1], &priority), ["Tetris (Japan)"]);
+    }
+}
@@ -0,0 +1,587 @@
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::normalize::fold;
+use crate::xml::GameEntry;
+
+// 搜索框的查询语言：
+//   mario land              多个词同时匹配（AND）
+//   "super mario"           带引号的短语
+//   -beta                   排除
+//   tetris OR columns       任意一组匹配（OR 优先级低于 AND）
+//   platform:"game boy"     按字段匹配：platform/region/lang/crc/md5/sha1/year/name/desc/maker/source
+//   mario*land  zel?a       通配符（* 任意多个字符，? 单个字符）
+//   /^super .* (usa)/       正则表达式，不区分大小写
+//   year:1990..1995         年份范围
+
+pub const HELP: &str = "多个词同时匹配；\"短语\"；-排除；A OR B；\n\
+字段：platform: region: lang: crc: md5: sha1: year: name: desc: maker: source:\n\
+通配符 * 和 ?；/正则/；year:1990..1995";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    // 未指定字段：名称、归档名、描述、年份（以及可选的全部字段）
+    Any,
+    Name,
+    Platform,
+    Region,
+    Language,
+    Crc,
+    Md5,
+    Sha1,
+    Year,
+    Description,
+    Manufacturer,
+    Library,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "name" | "名称" => Field::Name,
+            "platform" | "平台" => Field::Platform,
+            "region" | "区域" => Field::Region,
+            "lang" | "language" | "语言" => Field::Language,
+            "crc" | "crc32" => Field::Crc,
+            "md5" => Field::Md5,
+            "sha1" => Field::Sha1,
+            "year" | "年份" => Field::Year,
+            "desc" | "description" | "描述" => Field::Description,
+            "maker" | "manufacturer" | "厂商" => Field::Manufacturer,
+            "source" | "library" | "来源" => Field::Library,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    // 归一化（见 normalize::fold）后的子串
+    Text(String),
+    Regex(Regex),
+    // 年份范围（含两端）
+    Range(u32, u32),
+}
+
+impl Pattern {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Text(t) => fold(value).contains(t.as_str()),
+            Pattern::Regex(re) => re.is_match(value),
+            Pattern::Range(from, to) => value
+                .get(..4)
+                .and_then(|y| y.parse::<u32>().ok())
+                .map(|y| (*from..=*to).contains(&y))
+                .unwrap_or(false),
+        }
+    }
+
+    // 语言按逗号分隔的单个代码精确匹配
+    fn matches_token(&self, value: &str) -> bool {
+        value.split(',').map(str::trim).any(|tok| match self {
+            Pattern::Text(t) => fold(tok) == *t,
+            _ => self.matches(tok),
+        })
+    }
+
+    // 校验值按前缀匹配，可以只输入前几位
+    fn matches_hash(&self, value: &Option<String>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match self {
+            Pattern::Text(t) => value.starts_with(t.as_str()),
+            _ => self.matches(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    negate: bool,
+    field: Field,
+    pattern: Pattern,
+}
+
+// 模糊匹配允许的编辑距离：短词不容错，长词最多两处
+pub fn typo_tolerance(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// 按字母数字切分出的单词及其字节范围
+pub fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                out.push((s, &text[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        out.push((s, &text[s..]));
+    }
+    out
+}
+
+// 与关键字相差在容错范围内的单词
+fn fuzzy_words<'a>(term: &str, text: &'a str) -> impl Iterator<Item = (usize, &'a str, usize)> {
+    let tolerance = typo_tolerance(term);
+    // 带空格的短语不做模糊匹配
+    let enabled = tolerance > 0 && !term.contains(char::is_whitespace);
+    let term = term.to_string();
+    words(text)
+        .into_iter()
+        .filter(move |_| enabled)
+        .filter_map(move |(pos, word)| {
+            let distance = strsim::damerau_levenshtein(&fold(word), &term);
+            (distance <= tolerance).then_some((pos, word, distance))
+        })
+}
+
+// 关键字（小写）在字段中的得分：整词 > 词首 > 词中子串 > 拼写相近的词，
+// 字段以关键字开头再加分；不匹配时为 None
+fn text_score(term: &str, value: &str) -> Option<f32> {
+    let lower = fold(value);
+    if lower.contains(term) {
+        let words = words(&lower);
+        let mut score = if words.iter().any(|(_, w)| *w == term) {
+            3.0
+        } else if words.iter().any(|(_, w)| w.starts_with(term)) {
+            2.5
+        } else {
+            2.0
+        };
+        if lower.starts_with(term) {
+            score += 0.5;
+        }
+        return Some(score);
+    }
+    fuzzy_words(term, value)
+        .map(|(_, _, distance)| 1.5 - 0.5 * (distance as f32 - 1.0))
+        .reduce(f32::max)
+}
+
+// 与拼音/罗马字比较的形式：去掉空格，只接受两个以上的 ASCII 字符
+pub fn romanized_key(term: &str) -> Option<String> {
+    let compact: String = term.chars().filter(|c| !c.is_whitespace()).collect();
+    (compact.is_ascii() && compact.len() >= 2).then_some(compact)
+}
+
+// 用拼音/罗马字搜索中日文名称：全拼优先于拼音首字母，从头匹配再加分
+fn romanized_score(term: &str, g: &GameEntry) -> Option<f32> {
+    let romanized = g.romanized.as_ref()?;
+    let compact = romanized_key(term)?;
+    let score = |text: &str, base: f32| {
+        if text.starts_with(&compact) {
+            Some(base + 0.5)
+        } else if text.contains(&compact) {
+            Some(base)
+        } else {
+            None
+        }
+    };
+    let score = score(&romanized.full, 2.0).or_else(|| score(&romanized.initials, 1.5))?;
+    Some(score * ROMANIZED_WEIGHT)
+}
+
+// 各字段的权重：名称优先于描述、归档名和其他字段
+const NAME_WEIGHT: f32 = 1.0;
+const DESCRIPTION_WEIGHT: f32 = 0.8;
+const ARCHIVE_WEIGHT: f32 = 0.6;
+const OTHER_WEIGHT: f32 = 0.4;
+const ROMANIZED_WEIGHT: f32 = 0.9;
+
+impl Term {
+    // 名称类字段上的普通文本关键字，按相关度打分并允许拼写错误
+    fn is_ranked(&self) -> bool {
+        !self.negate
+            && matches!(self.field, Field::Any | Field::Name)
+            && matches!(self.pattern, Pattern::Text(_))
+    }
+
+    fn ranked_score(&self, term: &str, g: &GameEntry, all_fields: bool) -> Option<f32> {
+        let mut values: Vec<(f32, &str)> = vec![(NAME_WEIGHT, &g.name)];
+        values.extend(g.archive_name.as_deref().map(|v| (ARCHIVE_WEIGHT, v)));
+        if self.field == Field::Any {
+            values.extend(g.description.as_deref().map(|v| (DESCRIPTION_WEIGHT, v)));
+            values.extend(g.year.as_deref().map(|v| (OTHER_WEIGHT, v)));
+            if all_fields {
+                values.extend(
+                    g.fields
+                        .iter()
+                        .flat_map(|s| s.values.iter().map(|(_, v)| (OTHER_WEIGHT, v.as_str()))),
+                );
+            }
+        }
+        values
+            .into_iter()
+            .filter_map(|(weight, v)| text_score(term, v).map(|s| s * weight))
+            .chain(romanized_score(term, g))
+            .reduce(f32::max)
+    }
+
+    fn matches(&self, g: &GameEntry, all_fields: bool) -> bool {
+        let p = &self.pattern;
+        let opt = |f: &Option<String>| f.as_deref().map(|v| p.matches(v)).unwrap_or(false);
+        let hit = match self.field {
+            Field::Any => {
+                p.matches(&g.name)
+                    || opt(&g.archive_name)
+                    || opt(&g.description)
+                    || opt(&g.year)
+                    || (all_fields
+                        && g.fields
+                            .iter()
+                            .any(|s| s.values.iter().any(|(_, v)| p.matches(v))))
+            }
+            Field::Name => p.matches(&g.name) || opt(&g.archive_name),
+            Field::Platform => p.matches(&g.platform),
+            Field::Region => opt(&g.region),
+            Field::Language => g
+                .languages
+                .as_deref()
+                .map(|v| p.matches_token(v))
+                .unwrap_or(false),
+            Field::Crc => g.roms.iter().any(|r| p.matches_hash(&r.crc)),
+            Field::Md5 => g.roms.iter().any(|r| p.matches_hash(&r.md5)),
+            Field::Sha1 => g
+                .roms
+                .iter()
+                .chain(&g.disks)
+                .any(|r| p.matches_hash(&r.sha1)),
+            Field::Year => opt(&g.year),
+            Field::Description => opt(&g.description),
+            Field::Manufacturer => opt(&g.manufacturer),
+            Field::Library => p.matches(&g.library),
+        };
+        hit != self.negate
+    }
+
+    // 匹配得分，不匹配时为 None；排除项和按字段过滤的条件只判断是否匹配，不计分
+    fn score(&self, g: &GameEntry, all_fields: bool) -> Option<f32> {
+        match &self.pattern {
+            Pattern::Text(term) if self.is_ranked() => self.ranked_score(term, g, all_fields),
+            _ => self.matches(g, all_fields).then_some(0.0),
+        }
+    }
+}
+
+// 解析后的查询：若干组用 OR 连接，组内各项同时满足；空查询匹配全部
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    groups: Vec<Vec<Term>>,
+}
+
+// 查询语法错误，position 为出错处的字符位置（从 0 开始）
+#[derive(Debug, Clone)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "第 {} 个字符: {}", self.position + 1, self.message)
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        Parser::new(text).parse()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // 每组中参与相关度计算的关键字（已归一化），条目至少要在名称类字段中匹配它们，
+    // 可以用倒排索引缩小范围；组内没有这样的关键字时为空
+    pub fn ranked_terms(&self) -> Vec<Vec<&str>> {
+        self.groups
+            .iter()
+            .map(|terms| {
+                terms
+                    .iter()
+                    .filter(|t| t.is_ranked())
+                    .filter_map(|t| match &t.pattern {
+                        Pattern::Text(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // 相关度得分，各组取最高；不匹配时为 None，空查询的得分为 0
+    pub fn score(&self, g: &GameEntry, all_fields: bool) -> Option<f32> {
+        if self.groups.is_empty() {
+            return Some(0.0);
+        }
+        self.groups
+            .iter()
+            .filter_map(|terms| {
+                terms
+                    .iter()
+                    .map(|t| t.score(g, all_fields))
+                    .sum::<Option<f32>>()
+            })
+            .reduce(f32::max)
+    }
+
+    // 名称中需要高亮的字节范围：关键字的子串，或拼写相近的整个单词
+    pub fn highlight_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        let lower = fold(text);
+        let mut spans = Vec::new();
+        for term in self.groups.iter().flatten().filter(|t| t.is_ranked()) {
+            let Pattern::Text(term) = &term.pattern else {
+                continue;
+            };
+            // 小写后长度不变时，子串的位置可以直接用于原文
+            if lower.contains(term.as_str()) {
+                if lower.len() == text.len() {
+                    spans.extend(
+                        lower
+                            .match_indices(term.as_str())
+                            .map(|(s, m)| (s, s + m.len())),
+                    );
+                }
+                continue;
+            }
+            spans.extend(fuzzy_words(term, text).map(|(s, w, _)| (s, s + w.len())));
+        }
+        spans
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error<T>(&self, position: usize, message: impl Into<String>) -> Result<T, QueryError> {
+        Err(QueryError {
+            position,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    // 位置在末尾或空白处
+    fn at_break(&self, pos: usize) -> bool {
+        !matches!(self.chars.get(pos), Some(c) if !c.is_whitespace())
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // 读到下一个空白为止
+    fn read_word(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    // 读到结束符为止（不含结束符），缺少结束符时报错
+    fn read_until(&mut self, close: char, what: &str) -> Result<String, QueryError> {
+        let open = self.pos;
+        self.pos += 1;
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == close {
+                let s = self.chars[start..self.pos].iter().collect();
+                self.pos += 1;
+                return Ok(s);
+            }
+            // 正则中的 \/ 不结束表达式
+            if c == '\\' && close == '/' {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+        self.error(open, format!("{}缺少结束的 {}", what, close))
+    }
+
+    fn at_or(&self) -> bool {
+        let end = self.pos + 2;
+        self.chars.get(self.pos..end) == Some(&['O', 'R']) && self.at_break(end)
+    }
+
+    fn parse(mut self) -> Result<Query, QueryError> {
+        let mut groups = Vec::new();
+        let mut current: Vec<Term> = Vec::new();
+        loop {
+            self.skip_spaces();
+            if self.peek().is_none() {
+                break;
+            }
+            if self.at_or() {
+                if current.is_empty() {
+                    return self.error(self.pos, "OR 前面缺少搜索条件");
+                }
+                groups.push(std::mem::take(&mut current));
+                self.pos += 2;
+                continue;
+            }
+            current.push(self.parse_term()?);
+        }
+        if current.is_empty() && !groups.is_empty() {
+            return self.error(self.chars.len(), "OR 后面缺少搜索条件");
+        }
+        if !current.is_empty() {
+            groups.push(current);
+        }
+        Ok(Query { groups })
+    }
+
+    fn parse_term(&mut self) -> Result<Term, QueryError> {
+        let start = self.pos;
+        let negate = self.peek() == Some('-');
+        if negate {
+            self.pos += 1;
+            if self.at_break(self.pos) {
+                return self.error(start, "\"-\" 后面缺少要排除的内容");
+            }
+        }
+
+        // 字段前缀：冒号前全是字母。不是已知字段时按普通文字处理，
+        // 如 "Street Fighter II: The World Warrior" 中的 "II:"
+        let mut field = Field::Any;
+        let prefix_end = self.chars[self.pos..]
+            .iter()
+            .position(|c| !c.is_alphanumeric())
+            .map(|i| self.pos + i);
+        let known = prefix_end
+            .filter(|&e| e > self.pos && self.chars[e] == ':')
+            .and_then(|end| {
+                let name: String = self.chars[self.pos..end].iter().collect();
+                Field::from_name(&name).map(|f| (end, name, f))
+            });
+        if let Some((end, name, f)) = known {
+            field = f;
+            self.pos = end + 1;
+            if self.at_break(self.pos) {
+                return self.error(self.pos, format!("字段 {}: 缺少值", name));
+            }
+        }
+
+        let value_start = self.pos;
+        let pattern = match self.peek() {
+            Some('"') => {
+                let phrase = self.read_until('"', "短语")?;
+                if phrase.trim().is_empty() {
+                    return self.error(value_start, "短语为空");
+                }
+                Pattern::Text(fold(&phrase))
+            }
+            Some('/') => {
+                let expr = self.read_until('/', "正则表达式")?;
+                match RegexBuilder::new(&expr).case_insensitive(true).build() {
+                    Ok(re) => Pattern::Regex(re),
+                    Err(e) => {
+                        return self.error(
+                            value_start,
+                            format!(
+                                "无效的正则表达式: {}",
+                                // regex 的错误信息有多行，最后一行是原因
+                                e.to_string().lines().last().unwrap_or_default()
+                            ),
+                        );
+                    }
+                }
+            }
+            _ => {
+                let word = self.read_word();
+                if field == Field::Year {
+                    if let Some((from, to)) = word.split_once("..") {
+                        match (from.parse(), to.parse()) {
+                            (Ok(from), Ok(to)) => {
+                                return Ok(Term {
+                                    negate,
+                                    field,
+                                    pattern: Pattern::Range(from, to),
+                                })
+                            }
+                            _ => return self.error(value_start, "年份范围应为 1990..1995"),
+                        }
+                    }
+                }
+                if word.contains(['*', '?']) {
+                    Pattern::Regex(glob_regex(&word))
+                } else {
+                    Pattern::Text(fold(&word))
+                }
+            }
+        };
+        Ok(Term {
+            negate,
+            field,
+            pattern,
+        })
+    }
+}
+
+// 通配符转为不区分大小写的正则，不要求匹配整个字段
+fn glob_regex(glob: &str) -> Regex {
+    let mut expr = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            _ => expr.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    RegexBuilder::new(&expr)
+        .case_insensitive(true)
+        .build()
+        .expect("转义后的通配符总是合法的正则")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_field_prefix_is_literal_text() {
+        let title = "Street Fighter II: The World Warrior";
+        let query = Query::parse(title).unwrap();
+        assert_eq!(
+            query.ranked_terms(),
+            vec![vec!["street", "fighter", "ii:", "the", "world", "warrior"]]
+        );
+        assert!(!query.highlight_spans(title).is_empty());
+        assert!(Query::parse("-foo:bar").is_ok());
+    }
+
+    #[test]
+    fn known_fields_still_report_bad_values() {
+        assert!(Query::parse("platform:").is_err());
+        assert!(Query::parse("year:1990..x").is_err());
+        assert!(Query::parse("name:/(/").is_err());
+    }
+}
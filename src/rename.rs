@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+
+use crate::verify::{self, RomLookup, ScanProgress};
+use crate::xml::GameEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Hash,
+    Name,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameItem {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub game: String,
+    pub kind: MatchKind,
+    // 冲突说明；有冲突的条目默认不应用
+    pub conflict: Option<String>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RenamePlan {
+    pub root: PathBuf,
+    pub items: Vec<RenameItem>,
+    pub unmatched: Vec<PathBuf>,
+    pub unchanged: usize,
+}
+
+// 按名称匹配用的查找表：去掉括号标签后的标题 -> 游戏名列表
+pub struct NameLookup {
+    by_title: HashMap<String, Vec<String>>,
+}
+
+// 归一化标题："Foo Bar (USA) [b]" -> "foobar"
+fn normalize_title(name: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0i32;
+    for c in name.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ if depth <= 0 && c.is_alphanumeric() => out.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    out
+}
+
+impl NameLookup {
+    pub fn build(index: &[GameEntry]) -> Self {
+        let mut by_title: HashMap<String, Vec<String>> = HashMap::new();
+        for g in index {
+            let list = by_title.entry(normalize_title(&g.name)).or_default();
+            if !list.contains(&g.name) {
+                list.push(g.name.clone());
+            }
+        }
+        Self { by_title }
+    }
+
+    // 标题相同的候选中，选与完整文件名最相似的一个
+    fn find(&self, stem: &str) -> Option<&str> {
+        let candidates = self.by_title.get(&normalize_title(stem))?;
+        let stem = stem.to_lowercase();
+        candidates
+            .iter()
+            .map(|c| (c, strsim::normalized_levenshtein(&stem, &c.to_lowercase())))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| c.as_str())
+    }
+}
+
+// (游戏名, 新路径, 匹配方式)
+type FileMatch = (String, PathBuf, MatchKind);
+
+// 清理文件名，移除非法字符
+pub fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '\"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+fn file_ext(path: &Path) -> String {
+    path.extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default()
+}
+
+pub fn build_plan(
+    root: &Path,
+    lookup: &RomLookup,
+    names: &NameLookup,
+    progress: &ScanProgress,
+) -> RenamePlan {
+    let files: Vec<PathBuf> = fs::read_dir(root)
+        .map(|rd| {
+            rd.filter_map(|e| e.ok())
+                .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    progress.total.store(files.len(), Ordering::Relaxed);
+
+    let matched: Vec<(PathBuf, Option<FileMatch>)> = files
+        .into_par_iter()
+        .map(|from| {
+            let m = match_file(&from, lookup, names);
+            progress.done.fetch_add(1, Ordering::Relaxed);
+            (from, m)
+        })
+        .collect();
+
+    let mut plan = RenamePlan {
+        root: root.to_path_buf(),
+        ..Default::default()
+    };
+    for (from, m) in matched {
+        match m {
+            Some((_, to, _)) if to == from => plan.unchanged += 1,
+            Some((game, to, kind)) => plan.items.push(RenameItem {
+                from,
+                to,
+                game,
+                kind,
+                conflict: None,
+                enabled: true,
+            }),
+            None => plan.unmatched.push(from),
+        }
+    }
+    plan.items.sort_by(|a, b| a.from.cmp(&b.from));
+    plan.unmatched.sort();
+    mark_conflicts(&mut plan.items);
+    plan
+}
+
+// 先按校验值匹配，失败再按文件名模糊匹配
+fn match_file(from: &Path, lookup: &RomLookup, names: &NameLookup) -> Option<FileMatch> {
+    let parent = from.parent()?;
+    let zipped = verify::is_zip(from);
+    if let Ok(hashes) = verify::identify_file(from, |h| lookup.identify(h).is_some()) {
+        if let Some((game, rom)) = hashes.iter().find_map(|h| lookup.identify(h)) {
+            // zip 以游戏名命名，普通文件使用 DAT 中的 ROM 文件名
+            let new_name = if zipped || rom.is_empty() {
+                format!("{}{}", sanitize_filename(game), file_ext(from))
+            } else {
+                sanitize_filename(rom)
+            };
+            return Some((game.to_string(), parent.join(new_name), MatchKind::Hash));
+        }
+    }
+    let stem = from.file_stem()?.to_string_lossy();
+    let game = names.find(&stem)?;
+    let new_name = format!("{}{}", sanitize_filename(game), file_ext(from));
+    Some((game.to_string(), parent.join(new_name), MatchKind::Name))
+}
+
+// 两个路径是否指向同一个文件（大小写不敏感的文件系统上只差大小写的路径）
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(x), Ok(y)) => x.dev() == y.dev() && x.ino() == y.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(x), Ok(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+// 目标已被其他文件占用（不是源文件自身）
+fn target_taken(from: &Path, to: &Path) -> bool {
+    to.exists() && !same_file(from, to)
+}
+
+// 目标是另一项的源文件时不算冲突：执行时按依赖顺序重命名，循环借助临时文件名
+fn mark_conflicts(items: &mut [RenameItem]) {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for item in items.iter() {
+        *counts.entry(item.to.clone()).or_default() += 1;
+    }
+    let sources: HashSet<PathBuf> = items.iter().map(|i| i.from.clone()).collect();
+    for item in items.iter_mut() {
+        if counts.get(&item.to).copied().unwrap_or(0) > 1 {
+            item.conflict = Some("多个文件指向同一目标".to_string());
+        } else if target_taken(&item.from, &item.to) && !sources.contains(&item.to) {
+            item.conflict = Some("目标文件已存在".to_string());
+        }
+        if item.conflict.is_some() {
+            item.enabled = false;
+        }
+    }
+}
+
+// 同一目录下未被占用的临时文件名
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    (0..)
+        .map(|n| dir.join(format!(".{}.rename-{}", name, n)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+// 执行一组 (原路径, 新路径) 重命名。目标是另一项尚未移走的源文件时先等待，
+// 只剩互相依赖的循环（如互换）时把其中一个先移到临时文件名；
+// 只差大小写的重命名也经过临时文件名。返回已完成的重命名和错误列表
+fn rename_all(moves: &[(PathBuf, PathBuf)]) -> (Vec<(PathBuf, PathBuf)>, Vec<String>) {
+    let mut done = Vec::new();
+    let mut errors = Vec::new();
+    // (当前位置, 原路径, 新路径)
+    let mut pending: Vec<(PathBuf, &PathBuf, &PathBuf)> = moves
+        .iter()
+        .map(|(from, to)| (from.clone(), from, to))
+        .collect();
+    while !pending.is_empty() {
+        let sources: HashSet<PathBuf> = pending.iter().map(|(cur, _, _)| cur.clone()).collect();
+        let mut waiting = Vec::new();
+        let mut progressed = false;
+        for (cur, from, to) in pending {
+            if *to != cur && sources.contains(to) {
+                waiting.push((cur, from, to));
+                continue;
+            }
+            progressed = true;
+            if target_taken(&cur, to) {
+                errors.push(format!("目标文件已存在: {}", to.display()));
+                continue;
+            }
+            let result = if to.exists() {
+                // 大小写不敏感的文件系统上只改大小写
+                let temp = temp_path(&cur);
+                fs::rename(&cur, &temp).and_then(|()| fs::rename(&temp, to))
+            } else {
+                fs::rename(&cur, to)
+            };
+            match result {
+                Ok(()) => done.push((from.clone(), to.clone())),
+                Err(e) => errors.push(format!("{}: {}", cur.display(), e)),
+            }
+        }
+        if !progressed {
+            // 剩下的都在循环中：把一个被其他项作为目标的文件移到临时文件名，腾出它的位置
+            let i = waiting
+                .iter()
+                .position(|(cur, _, _)| waiting.iter().any(|(_, _, to)| *to == cur))
+                .unwrap_or(0);
+            let (cur, from, to) = waiting.remove(i);
+            let temp = temp_path(&cur);
+            match fs::rename(&cur, &temp) {
+                Ok(()) => waiting.push((temp, from, to)),
+                Err(e) => errors.push(format!("{}: {}", cur.display(), e)),
+            }
+        }
+        pending = waiting;
+    }
+    (done, errors)
+}
+
+// 一次批量重命名的记录，用于撤销
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RenameBatch {
+    pub root: PathBuf,
+    pub renames: Vec<(PathBuf, PathBuf)>,
+}
+
+fn journal_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("retro_game_search").join("rename_journal.json"))
+}
+
+pub fn load_journal() -> Vec<RenameBatch> {
+    journal_path()
+        .and_then(|p| fs::read(p).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_journal(batches: &[RenameBatch]) -> Result<()> {
+    let path = journal_path().ok_or_else(|| anyhow!("无法获取配置目录"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("无法创建配置目录")?;
+    }
+    let data = serde_json::to_vec_pretty(batches)?;
+    fs::write(&path, data).with_context(|| format!("无法写入 {}", path.display()))
+}
+
+// 批量重命名或撤销的结果
+#[derive(Debug, Default)]
+pub struct RenameOutcome {
+    pub count: usize,
+    pub errors: Vec<String>,
+    // 重命名已完成但撤销记录没有保存成功
+    pub journal_error: Option<String>,
+}
+
+// 执行启用的重命名并记录到撤销日志，单个文件失败不影响其他文件
+pub fn apply_plan(plan: &RenamePlan) -> RenameOutcome {
+    let moves: Vec<(PathBuf, PathBuf)> = plan
+        .items
+        .iter()
+        .filter(|i| i.enabled)
+        .map(|i| (i.from.clone(), i.to.clone()))
+        .collect();
+    let (done, errors) = rename_all(&moves);
+    let mut outcome = RenameOutcome {
+        count: done.len(),
+        errors,
+        journal_error: None,
+    };
+    if !done.is_empty() {
+        let mut journal = load_journal();
+        journal.push(RenameBatch {
+            root: plan.root.clone(),
+            renames: done,
+        });
+        if let Err(e) = save_journal(&journal) {
+            outcome.journal_error = Some(format!("{:#}", e));
+        }
+    }
+    outcome
+}
+
+// 撤销最近一次批量重命名；没能撤销的条目留在日志中，可以再次撤销
+pub fn undo_last() -> Result<RenameOutcome> {
+    let mut journal = load_journal();
+    let batch = journal
+        .pop()
+        .ok_or_else(|| anyhow!("没有可撤销的批量重命名"))?;
+    let moves: Vec<(PathBuf, PathBuf)> = batch
+        .renames
+        .iter()
+        .rev()
+        .map(|(from, to)| (to.clone(), from.clone()))
+        .collect();
+    let (undone, errors) = rename_all(&moves);
+    let remaining: Vec<(PathBuf, PathBuf)> = batch
+        .renames
+        .iter()
+        .filter(|(from, to)| !undone.iter().any(|(t, f)| t == to && f == from))
+        .cloned()
+        .collect();
+    if !remaining.is_empty() {
+        journal.push(RenameBatch {
+            root: batch.root,
+            renames: remaining,
+        });
+    }
+    let journal_error = save_journal(&journal).err().map(|e| format!("{:#}", e));
+    Ok(RenameOutcome {
+        count: undone.len(),
+        errors,
+        journal_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rgm-rename-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn renames_chains_and_swaps() {
+        let dir = temp_dir("chain");
+        for (name, content) in [("a", "A"), ("b", "B"), ("x", "X"), ("y", "Y")] {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        // a -> b -> c 的链，以及 x、y 互换
+        let moves = [("a", "b"), ("b", "c"), ("x", "y"), ("y", "x")]
+            .map(|(from, to)| (dir.join(from), dir.join(to)));
+        let (done, errors) = rename_all(&moves);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(done.len(), 4);
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!((read("b"), read("c")), ("A".to_string(), "B".to_string()));
+        assert_eq!((read("x"), read("y")), ("Y".to_string(), "X".to_string()));
+        assert!(!dir.join("a").exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_files_that_took_the_target() {
+        let dir = temp_dir("taken");
+        fs::write(dir.join("a"), "A").unwrap();
+        fs::write(dir.join("b"), "B").unwrap();
+        let (done, errors) = rename_all(&[(dir.join("a"), dir.join("b"))]);
+        assert!(done.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "B");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
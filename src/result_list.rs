@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::clones::ResultGroup;
+
+// 虚拟化的结果列表：把分组展开成一行行卡片，并记录每行的纵向位置，
+// 绘制时只布局落在可见区域内的行，因此结果再多也不需要截断
+
+// 列表中的一行
+#[derive(Debug, Clone, Copy)]
+pub enum ResultRow {
+    // 游戏卡片；member 为分组展开后的其他版本，需要缩进显示
+    Card { game: usize, member: bool },
+    // 组首下方展开/收起其他版本的按钮
+    Toggle { head: usize, count: usize },
+}
+
+// 计算行高用到的尺寸，随字体和界面样式变化
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowMetrics {
+    // 一行文字的高度与控件间距
+    pub line: f32,
+    pub spacing: f32,
+    // 卡片除文字外的额外高度（边框内边距和卡片之间的空白）
+    pub card_padding: f32,
+    // 展开按钮一行的高度
+    pub toggle: f32,
+}
+
+impl RowMetrics {
+    fn card_height(&self, lines: usize) -> f32 {
+        let lines = lines.max(1) as f32;
+        lines * self.line + (lines - 1.0) * self.spacing + self.card_padding
+    }
+}
+
+#[derive(Debug)]
+pub struct ResultList {
+    // 生成该布局的输入，变化时需要重建
+    results: Arc<Vec<usize>>,
+    grouped: bool,
+    expanded: HashSet<usize>,
+    metrics: RowMetrics,
+    pub rows: Vec<ResultRow>,
+    // 每行顶部的位置，最后多一项为列表总高度
+    offsets: Vec<f32>,
+}
+
+impl ResultList {
+    // card_lines 给出条目卡片显示的文字行数
+    pub fn build(
+        results: &Arc<Vec<usize>>,
+        groups: &[ResultGroup],
+        grouped: bool,
+        expanded: &HashSet<usize>,
+        metrics: RowMetrics,
+        card_lines: impl Fn(usize) -> usize,
+    ) -> Self {
+        let mut rows = Vec::with_capacity(groups.len());
+        for group in groups {
+            rows.push(ResultRow::Card {
+                game: group.head,
+                member: false,
+            });
+            if group.members.is_empty() {
+                continue;
+            }
+            rows.push(ResultRow::Toggle {
+                head: group.head,
+                count: group.members.len(),
+            });
+            if expanded.contains(&group.head) {
+                rows.extend(group.members.iter().map(|&game| ResultRow::Card {
+                    game,
+                    member: true,
+                }));
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(rows.len() + 1);
+        let mut y = 0.0;
+        for row in &rows {
+            offsets.push(y);
+            y += match row {
+                ResultRow::Card { game, .. } => metrics.card_height(card_lines(*game)),
+                ResultRow::Toggle { .. } => metrics.toggle,
+            };
+        }
+        offsets.push(y);
+
+        Self {
+            results: Arc::clone(results),
+            grouped,
+            expanded: expanded.clone(),
+            metrics,
+            rows,
+            offsets,
+        }
+    }
+
+    pub fn is_current(
+        &self,
+        results: &Arc<Vec<usize>>,
+        grouped: bool,
+        expanded: &HashSet<usize>,
+        metrics: RowMetrics,
+    ) -> bool {
+        Arc::ptr_eq(&self.results, results)
+            && self.grouped == grouped
+            && self.expanded == *expanded
+            && self.metrics == metrics
+    }
+
+    pub fn total_height(&self) -> f32 {
+        self.offsets.last().copied().unwrap_or(0.0)
+    }
+
+    // 第 i 行的顶部位置和高度
+    pub fn row_span(&self, i: usize) -> (f32, f32) {
+        (self.offsets[i], self.offsets[i + 1] - self.offsets[i])
+    }
+
+    // 与纵向区间 [top, bottom) 相交的行
+    pub fn visible(&self, top: f32, bottom: f32) -> Range<usize> {
+        let rows = self.rows.len();
+        let start = self.offsets[1..].partition_point(|&end| end <= top).min(rows);
+        let end = self.offsets[..rows].partition_point(|&y| y < bottom).max(start);
+        start..end
+    }
+}
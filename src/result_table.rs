@@ -0,0 +1,346 @@
+use std::cmp::Ordering;
+
+use rayon::prelude::*;
+
+use crate::xml::GameEntry;
+
+// 结果的表格视图：可选的列、列宽和多列排序，布局随其他设置一起保存
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum TableColumn {
+    Name,
+    Platform,
+    Region,
+    Languages,
+    ArchiveName,
+    Serial,
+    Crc,
+    SourceFile,
+}
+
+impl TableColumn {
+    pub const ALL: [TableColumn; 8] = [
+        TableColumn::Name,
+        TableColumn::Platform,
+        TableColumn::Region,
+        TableColumn::Languages,
+        TableColumn::ArchiveName,
+        TableColumn::Serial,
+        TableColumn::Crc,
+        TableColumn::SourceFile,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TableColumn::Name => "名称",
+            TableColumn::Platform => "平台",
+            TableColumn::Region => "区域",
+            TableColumn::Languages => "语言",
+            TableColumn::ArchiveName => "归档名",
+            TableColumn::Serial => "序列号",
+            TableColumn::Crc => "CRC32",
+            TableColumn::SourceFile => "来源文件",
+        }
+    }
+
+    fn default_width(self) -> f32 {
+        match self {
+            TableColumn::Name => 320.0,
+            TableColumn::SourceFile => 240.0,
+            TableColumn::ArchiveName => 200.0,
+            TableColumn::Platform => 160.0,
+            _ => 90.0,
+        }
+    }
+
+    // 单元格中显示的值，没有时为 None
+    pub fn value(self, g: &GameEntry) -> Option<&str> {
+        match self {
+            TableColumn::Name => Some(&g.name),
+            TableColumn::Platform => Some(&g.platform),
+            TableColumn::Region => g.region.as_deref(),
+            TableColumn::Languages => g.languages.as_deref(),
+            TableColumn::ArchiveName => g.archive_name.as_deref(),
+            // No-Intro 的序列号在 <serials>/<details> 等元素的属性中，取第一个
+            TableColumn::Serial => g
+                .fields
+                .iter()
+                .flat_map(|s| s.values.iter())
+                .find(|(k, _)| k.to_ascii_lowercase().contains("serial"))
+                .map(|(_, v)| v.as_str()),
+            // 多个 ROM 时取第一个
+            TableColumn::Crc => g.roms.iter().find_map(|r| r.crc.as_deref()),
+            TableColumn::SourceFile => Some(&g.file_path),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ColumnState {
+    pub column: TableColumn,
+    pub visible: bool,
+    pub width: f32,
+}
+
+// 一个排序键；多个键时按顺序比较
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SortKey {
+    pub column: TableColumn,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TableLayout {
+    pub columns: Vec<ColumnState>,
+    pub sort: Vec<SortKey>,
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self {
+            columns: TableColumn::ALL
+                .iter()
+                .map(|&column| ColumnState {
+                    column,
+                    visible: !matches!(column, TableColumn::Serial | TableColumn::Crc),
+                    width: column.default_width(),
+                })
+                .collect(),
+            sort: Vec::new(),
+        }
+    }
+}
+
+impl TableLayout {
+    // 读取旧设置后补上缺少的列、去掉重复的列和排序键
+    pub fn normalize(&mut self) {
+        let mut seen = Vec::new();
+        self.columns.retain(|c| {
+            let first = !seen.contains(&c.column);
+            seen.push(c.column);
+            first
+        });
+        for column in TableColumn::ALL {
+            if !seen.contains(&column) {
+                self.columns.push(ColumnState {
+                    column,
+                    visible: false,
+                    width: column.default_width(),
+                });
+            }
+        }
+        // 全部隐藏时没有表头可点，界面上只能从菜单恢复，至少显示名称列
+        if !self.columns.iter().any(|c| c.visible) {
+            if let Some(name) = self
+                .columns
+                .iter_mut()
+                .find(|c| c.column == TableColumn::Name)
+            {
+                name.visible = true;
+            }
+        }
+        let mut sorted = Vec::new();
+        self.sort.retain(|k| {
+            let first = !sorted.contains(&k.column);
+            sorted.push(k.column);
+            first
+        });
+    }
+
+    pub fn visible(&self) -> impl Iterator<Item = &ColumnState> {
+        self.columns.iter().filter(|c| c.visible)
+    }
+
+    // 列在排序键中的位置和方向，用于在表头显示
+    pub fn sort_of(&self, column: TableColumn) -> Option<(usize, bool)> {
+        self.sort
+            .iter()
+            .position(|k| k.column == column)
+            .map(|i| (i, self.sort[i].descending))
+    }
+
+    // 点击表头：单击只按该列排序，再次单击反转方向；
+    // additive（按住 Shift）时追加为次要排序键或反转已有键的方向
+    pub fn click_header(&mut self, column: TableColumn, additive: bool) {
+        let existing = self.sort.iter().position(|k| k.column == column);
+        if additive {
+            match existing {
+                Some(i) => self.sort[i].descending = !self.sort[i].descending,
+                None => self.sort.push(SortKey {
+                    column,
+                    descending: false,
+                }),
+            }
+        } else {
+            let descending = match (existing, self.sort.len()) {
+                (Some(i), 1) => !self.sort[i].descending,
+                _ => false,
+            };
+            self.sort = vec![SortKey { column, descending }];
+        }
+    }
+}
+
+// 按排序键排列结果；值相同的保持原来的顺序（即相关度顺序），缺少值的总是排在最后
+pub fn sort_results(index: &[GameEntry], results: &[usize], sort: &[SortKey]) -> Vec<usize> {
+    if sort.is_empty() {
+        return results.to_vec();
+    }
+    // 预先取出小写后的值，避免比较时重复分配
+    let keys: Vec<Vec<Option<String>>> = sort
+        .iter()
+        .map(|k| {
+            results
+                .par_iter()
+                .map(|&i| k.column.value(&index[i]).map(str::to_lowercase))
+                .collect()
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..results.len()).collect();
+    order.par_sort_by(|&a, &b| {
+        for (k, values) in sort.iter().zip(&keys) {
+            let ord = match (&values[a], &values[b]) {
+                (Some(x), Some(y)) if k.descending => y.cmp(x),
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    });
+    order.into_iter().map(|p| results[p]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::dat::DatSource;
+
+    fn parse(xml: &str) -> Vec<GameEntry> {
+        let source = DatSource {
+            path: Path::new("Test - Platform.xml"),
+            member: None,
+        };
+        crate::xml::parse_games(xml.as_bytes(), &source).unwrap().1
+    }
+
+    fn key(column: TableColumn, descending: bool) -> SortKey {
+        SortKey { column, descending }
+    }
+
+    #[test]
+    fn normalize_restores_columns_and_keeps_one_visible() {
+        let mut layout = TableLayout {
+            columns: vec![
+                ColumnState {
+                    column: TableColumn::Region,
+                    visible: false,
+                    width: 50.0,
+                },
+                ColumnState {
+                    column: TableColumn::Region,
+                    visible: true,
+                    width: 60.0,
+                },
+            ],
+            sort: vec![
+                key(TableColumn::Region, false),
+                key(TableColumn::Region, true),
+            ],
+        };
+        layout.normalize();
+        assert_eq!(layout.columns.len(), TableColumn::ALL.len());
+        assert_eq!(layout.columns[0].width, 50.0);
+        assert_eq!(layout.sort, [key(TableColumn::Region, false)]);
+        let visible: Vec<TableColumn> = layout.visible().map(|c| c.column).collect();
+        assert_eq!(visible, [TableColumn::Name]);
+
+        // 已有可见的列时不改动
+        layout.columns.iter_mut().for_each(|c| c.visible = false);
+        layout.columns[0].visible = true;
+        layout.normalize();
+        let visible: Vec<TableColumn> = layout.visible().map(|c| c.column).collect();
+        assert_eq!(visible, [TableColumn::Region]);
+    }
+
+    #[test]
+    fn click_header_toggles_and_adds_keys() {
+        let mut layout = TableLayout::default();
+        layout.click_header(TableColumn::Name, false);
+        assert_eq!(layout.sort, [key(TableColumn::Name, false)]);
+        layout.click_header(TableColumn::Name, false);
+        assert_eq!(layout.sort, [key(TableColumn::Name, true)]);
+
+        // Shift：追加次要排序键，再次点击只反转该键
+        layout.click_header(TableColumn::Region, true);
+        assert_eq!(
+            layout.sort,
+            [
+                key(TableColumn::Name, true),
+                key(TableColumn::Region, false)
+            ]
+        );
+        layout.click_header(TableColumn::Region, true);
+        assert_eq!(
+            layout.sort,
+            [key(TableColumn::Name, true), key(TableColumn::Region, true)]
+        );
+        assert_eq!(layout.sort_of(TableColumn::Region), Some((1, true)));
+
+        // 不按 Shift 点击多键排序中的列：只按该列升序
+        layout.click_header(TableColumn::Region, false);
+        assert_eq!(layout.sort, [key(TableColumn::Region, false)]);
+        assert_eq!(layout.sort_of(TableColumn::Name), None);
+    }
+
+    #[test]
+    fn sorts_by_several_keys_and_keeps_missing_values_last() {
+        let games = parse(
+            r#"<datafile>
+                <game name="b" region="USA"></game>
+                <game name="a"></game>
+                <game name="C" region="Japan"></game>
+                <game name="a" region="usa"></game>
+                <game name="d" region="Japan"></game>
+            </datafile>"#,
+        );
+        let results = [0, 1, 2, 3, 4];
+        let names = |order: Vec<usize>| -> Vec<String> {
+            order
+                .into_iter()
+                .map(|i| {
+                    format!(
+                        "{}/{}",
+                        games[i].name,
+                        games[i].region.as_deref().unwrap_or("-")
+                    )
+                })
+                .collect()
+        };
+
+        // 地区升序（不区分大小写，缺少地区的排最后），同一地区内名称降序
+        let sort = [
+            key(TableColumn::Region, false),
+            key(TableColumn::Name, true),
+        ];
+        assert_eq!(
+            names(sort_results(&games, &results, &sort)),
+            ["d/Japan", "C/Japan", "b/USA", "a/usa", "a/-"]
+        );
+
+        // 降序时缺少值的仍在最后；完全相同的键保持原来的顺序
+        let sort = [key(TableColumn::Region, true)];
+        assert_eq!(
+            names(sort_results(&games, &results, &sort)),
+            ["b/USA", "a/usa", "C/Japan", "d/Japan", "a/-"]
+        );
+        assert_eq!(sort_results(&games, &results, &[]), results);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+
+use crate::normalize::fold;
+use crate::query::{romanized_key, typo_tolerance, words, Query};
+use crate::xml::GameEntry;
+
+// 搜索用的倒排索引：单词 -> 条目下标，以及平台、区域、语言、来源库的位图。
+// 查询时先用它们缩小候选范围，再由 Query::score 逐条精确匹配和打分，
+// 因此这里只需要保证候选是匹配结果的超集。
+// 条目追加到索引末尾时可以增量扩展；下标变化（重载某个文件）后要重建，
+// 在此之前没有建索引的条目一律作为候选
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    // 已建索引的条目数，下标从 0 到 len
+    len: usize,
+    // 名称、归档名、描述、年份中的单词（归一化后）
+    words: Postings,
+    // 「全部字段」中的单词
+    field_words: Postings,
+    // 中日文名称的全拼和拼音首字母
+    romanized: Postings,
+    platforms: HashMap<String, RoaringBitmap>,
+    // 区域按小写的整个字段值记录，筛选时做子串匹配
+    regions: HashMap<String, RoaringBitmap>,
+    // 语言按逗号分隔的单个代码（小写）记录
+    languages: HashMap<String, RoaringBitmap>,
+    libraries: HashMap<String, RoaringBitmap>,
+}
+
+// 单词 -> 包含它的条目下标（升序）。
+// 每次按键都要在整个词表中查子串和拼写相近的词，因此额外建两种分桶：
+// 单词中长度 1~3 的片段 -> 单词，用于子串查找；字符数 -> 单词，用于模糊匹配
+#[derive(Debug, Default)]
+struct Postings {
+    tokens: Vec<Token>,
+    by_text: HashMap<String, u32>,
+    grams: HashMap<Gram, Vec<u32>>,
+    by_len: Vec<Vec<u32>>,
+}
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    chars: usize,
+    // 出现过的字符（按码位取模的位图），用于在计算编辑距离前快速排除
+    mask: u64,
+    ids: Vec<u32>,
+}
+
+// 最多三个字符的片段，不足的位置为 '\0'
+type Gram = [char; 3];
+
+fn gram(chars: &[char]) -> Gram {
+    let mut g = ['\0'; 3];
+    g[..chars.len()].copy_from_slice(chars);
+    g
+}
+
+fn char_mask(chars: &[char]) -> u64 {
+    chars.iter().fold(0, |m, &c| m | 1 << (c as u32 % 64))
+}
+
+impl Postings {
+    fn add(&mut self, token: &str, id: u32) {
+        if let Some(&t) = self.by_text.get(token) {
+            // 同一条目中重复出现的单词只记一次
+            let ids = &mut self.tokens[t as usize].ids;
+            if ids.last() != Some(&id) {
+                ids.push(id);
+            }
+            return;
+        }
+        let t = self.tokens.len() as u32;
+        let chars: Vec<char> = token.chars().collect();
+        let mut grams: Vec<Gram> = (1..=3).flat_map(|n| chars.windows(n).map(gram)).collect();
+        grams.sort_unstable();
+        grams.dedup();
+        for g in grams {
+            self.grams.entry(g).or_default().push(t);
+        }
+        if self.by_len.len() <= chars.len() {
+            self.by_len.resize_with(chars.len() + 1, Vec::new);
+        }
+        self.by_len[chars.len()].push(t);
+        self.by_text.insert(token.to_string(), t);
+        self.tokens.push(Token {
+            text: token.to_string(),
+            chars: chars.len(),
+            mask: char_mask(&chars),
+            ids: vec![id],
+        });
+    }
+
+    fn union(&self, tokens: impl Iterator<Item = u32>) -> RoaringBitmap {
+        let mut out = RoaringBitmap::new();
+        for t in tokens {
+            out.extend(self.tokens[t as usize].ids.iter().copied());
+        }
+        out
+    }
+
+    // 包含 part 的单词所在的条目：短片段直接查表，较长的取各三字片段所含单词的交集再核对
+    fn containing(&self, part: &str) -> RoaringBitmap {
+        let chars: Vec<char> = part.chars().collect();
+        if chars.is_empty() {
+            return RoaringBitmap::new();
+        }
+        if chars.len() <= 3 {
+            let tokens = self.grams.get(&gram(&chars)).map(Vec::as_slice);
+            return self.union(tokens.unwrap_or_default().iter().copied());
+        }
+        let mut lists: Vec<&[u32]> = Vec::new();
+        for w in chars.windows(3) {
+            match self.grams.get(&gram(w)) {
+                Some(list) => lists.push(list),
+                None => return RoaringBitmap::new(),
+            }
+        }
+        lists.sort_by_key(|l| l.len());
+        let (first, rest) = lists.split_first().expect("至少有一个三字片段");
+        // 各列表均为升序，可以二分查找
+        let tokens = first
+            .iter()
+            .copied()
+            .filter(|t| rest.iter().all(|l| l.binary_search(t).is_ok()))
+            .filter(|&t| self.tokens[t as usize].text.contains(part));
+        self.union(tokens)
+    }
+
+    // 与 term 的编辑距离不超过 tolerance 的单词所在的条目；只比较长度相近、字符组成相近的单词
+    fn similar(&self, term: &str, tolerance: usize) -> RoaringBitmap {
+        let chars: Vec<char> = term.chars().collect();
+        let mask = char_mask(&chars);
+        let lengths = chars.len().saturating_sub(tolerance)..=chars.len() + tolerance;
+        let tokens = lengths
+            .filter_map(|len| self.by_len.get(len))
+            .flatten()
+            .copied()
+            .filter(|&t| {
+                let token = &self.tokens[t as usize];
+                // 每处编辑最多改变两个字符的出现与否
+                (token.mask ^ mask).count_ones() as usize <= 2 * tolerance
+                    && token.chars.abs_diff(chars.len()) <= tolerance
+                    && strsim::damerau_levenshtein(&token.text, term) <= tolerance
+            });
+        self.union(tokens)
+    }
+
+    // 关键字可能出现在哪些条目中：关键字的每个单词都是某个单词的子串，
+    // 或者整个关键字与某个单词拼写相近（与 Query 的模糊匹配规则一致）
+    fn lookup(&self, term: &str, parts: &[&str]) -> RoaringBitmap {
+        let mut hits = parts
+            .iter()
+            .map(|part| self.containing(part))
+            .reduce(|a, b| a & b)
+            .unwrap_or_default();
+        let tolerance = typo_tolerance(term);
+        if tolerance > 0 && !term.contains(char::is_whitespace) {
+            hits |= self.similar(term, tolerance);
+        }
+        hits
+    }
+}
+
+// 单个条目要写入索引的单词，建索引时并行计算
+struct EntryTokens {
+    words: Vec<String>,
+    field_words: Vec<String>,
+    romanized: Vec<String>,
+}
+
+fn folded_words(values: impl Iterator<Item = impl AsRef<str>>) -> Vec<String> {
+    values
+        .flat_map(|v| {
+            let folded = fold(v.as_ref());
+            words(&folded)
+                .into_iter()
+                .map(|(_, w)| w.to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn entry_tokens(g: &GameEntry) -> EntryTokens {
+    let values = [
+        Some(&g.name),
+        g.archive_name.as_ref(),
+        g.description.as_ref(),
+        g.year.as_ref(),
+    ];
+    EntryTokens {
+        words: folded_words(values.into_iter().flatten()),
+        field_words: folded_words(
+            g.fields
+                .iter()
+                .flat_map(|s| s.values.iter().map(|(_, v)| v)),
+        ),
+        romanized: g
+            .romanized
+            .iter()
+            .flat_map(|r| [r.full.clone(), r.initials.clone()])
+            .collect(),
+    }
+}
+
+fn add_facet(map: &mut HashMap<String, RoaringBitmap>, key: &str, id: u32) {
+    match map.get_mut(key) {
+        Some(ids) => {
+            ids.insert(id);
+        }
+        None => {
+            map.insert(key.to_string(), RoaringBitmap::from_iter([id]));
+        }
+    }
+}
+
+fn union<'a>(sets: impl Iterator<Item = &'a RoaringBitmap>) -> RoaringBitmap {
+    sets.fold(RoaringBitmap::new(), |acc, ids| acc | ids)
+}
+
+impl SearchIndex {
+    pub fn build(index: &[GameEntry]) -> Self {
+        let mut search = Self::default();
+        search.extend(index);
+        search
+    }
+
+    // 为新追加到索引末尾的条目建索引
+    pub fn extend(&mut self, index: &[GameEntry]) {
+        let start = self.len.min(index.len());
+        let tokens: Vec<EntryTokens> = index[start..].par_iter().map(entry_tokens).collect();
+        for (i, (g, t)) in index[start..].iter().zip(tokens).enumerate() {
+            let id = (start + i) as u32;
+            for w in &t.words {
+                self.words.add(w, id);
+            }
+            for w in &t.field_words {
+                self.field_words.add(w, id);
+            }
+            for w in &t.romanized {
+                self.romanized.add(w, id);
+            }
+            add_facet(&mut self.platforms, &g.platform, id);
+            if let Some(region) = &g.region {
+                add_facet(&mut self.regions, &region.to_lowercase(), id);
+            }
+            for lang in g.languages.iter().flat_map(|s| s.split(',')) {
+                add_facet(&mut self.languages, &lang.trim().to_lowercase(), id);
+            }
+            add_facet(&mut self.libraries, &g.library, id);
+        }
+        self.len = index.len();
+    }
+
+    // 一个关键字的候选；关键字中没有字母数字时无法用索引缩小范围
+    fn term_candidates(&self, term: &str, all_fields: bool) -> Option<RoaringBitmap> {
+        let parts: Vec<&str> = words(term).into_iter().map(|(_, w)| w).collect();
+        if parts.is_empty() {
+            return None;
+        }
+        let mut hits = self.words.lookup(term, &parts);
+        if all_fields {
+            hits |= self.field_words.lookup(term, &parts);
+        }
+        if let Some(key) = romanized_key(term) {
+            hits |= self.romanized.containing(&key);
+        }
+        Some(hits)
+    }
+
+    // 查询的候选：各组候选的并集，组内为各关键字候选的交集。
+    // 有任何一组无法缩小范围时返回 None
+    fn query_candidates(&self, query: &Query, all_fields: bool) -> Option<RoaringBitmap> {
+        let mut out = RoaringBitmap::new();
+        for terms in query.ranked_terms() {
+            let group = terms
+                .iter()
+                .filter_map(|t| self.term_candidates(t, all_fields))
+                .reduce(|a, b| a & b)?;
+            out |= group;
+        }
+        Some(out)
+    }
+
+    // 可能满足查询和筛选条件的条目下标（升序），包括尚未建索引的条目
+    #[allow(clippy::too_many_arguments)]
+    pub fn candidates(
+        &self,
+        index_len: usize,
+        query: &Query,
+        platforms: &[String],
+        region: &str,
+        language: &str,
+        library: &str,
+        all_fields: bool,
+    ) -> Vec<usize> {
+        let mut filters: Vec<RoaringBitmap> = Vec::new();
+        if !platforms.is_empty() {
+            filters.push(union(
+                platforms.iter().filter_map(|p| self.platforms.get(p)),
+            ));
+        }
+        let region = region.trim().to_lowercase();
+        if !region.is_empty() {
+            filters.push(union(
+                self.regions
+                    .iter()
+                    .filter(|(r, _)| r.contains(&region))
+                    .map(|(_, ids)| ids),
+            ));
+        }
+        let language = language.trim().to_lowercase();
+        if !language.is_empty() {
+            filters.push(self.languages.get(&language).cloned().unwrap_or_default());
+        }
+        if !library.is_empty() {
+            filters.push(self.libraries.get(library).cloned().unwrap_or_default());
+        }
+        if !query.is_empty() {
+            filters.extend(self.query_candidates(query, all_fields));
+        }
+
+        let indexed = self.len.min(index_len);
+        let mut ids: Vec<usize> = match filters.into_iter().reduce(|a, b| a & b) {
+            Some(set) => set.iter().map(|i| i as usize).collect(),
+            None => (0..indexed).collect(),
+        };
+        ids.extend(indexed..index_len);
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &str = "super mario bros zelda link awakening final fantasy ii a \
+        marios tetris fantasia finale supermario zeldas link2";
+
+    fn words() -> Vec<&'static str> {
+        WORDS.split_whitespace().collect()
+    }
+
+    fn postings() -> Postings {
+        let mut p = Postings::default();
+        for (id, w) in words().into_iter().enumerate() {
+            p.add(w, id as u32);
+            // 重复的单词只记一次
+            p.add(w, id as u32);
+        }
+        p
+    }
+
+    // 逐个单词比较的结果，作为对照
+    fn naive(pred: impl Fn(&str) -> bool) -> RoaringBitmap {
+        words()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, w)| pred(w))
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    #[test]
+    fn containing_matches_substring_scan() {
+        let p = postings();
+        for part in [
+            "a", "ar", "ari", "mario", "tasy", "ntasi", "ink2", "x", "zeldaz",
+        ] {
+            assert_eq!(p.containing(part), naive(|w| w.contains(part)), "{}", part);
+        }
+    }
+
+    #[test]
+    fn similar_matches_edit_distance_scan() {
+        let p = postings();
+        for (term, tolerance) in [("zelad", 1), ("marjo", 1), ("fantsay", 1), ("fianle", 2)] {
+            let expected = naive(|w| strsim::damerau_levenshtein(w, term) <= tolerance);
+            assert_eq!(p.similar(term, tolerance), expected, "{}", term);
+            assert!(!expected.is_empty(), "{}", term);
+        }
+    }
+}
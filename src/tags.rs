@@ -0,0 +1,388 @@
+// No-Intro/TOSEC 风格名称中的标签：
+//   "Foo (USA, Europe) (En,Fr) (Rev 2) (Beta) (Proto) [b]"
+// 圆括号内是地区、语言、修订号和开发状态，方括号内是 dump 标记（[b] 损坏、[h] 修改、[p] 盗版等）
+
+// 可用于筛选的标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum TagFlag {
+    Beta,
+    Proto,
+    Demo,
+    Unlicensed,
+    Pirate,
+    Hack,
+    BadDump,
+}
+
+impl TagFlag {
+    pub const ALL: [TagFlag; 7] = [
+        TagFlag::Beta,
+        TagFlag::Proto,
+        TagFlag::Demo,
+        TagFlag::Unlicensed,
+        TagFlag::Pirate,
+        TagFlag::Hack,
+        TagFlag::BadDump,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TagFlag::Beta => "测试版 (Beta)",
+            TagFlag::Proto => "原型 (Proto)",
+            TagFlag::Demo => "演示版 (Demo)",
+            TagFlag::Unlicensed => "非授权 (Unl)",
+            TagFlag::Pirate => "盗版 (Pirate)",
+            TagFlag::Hack => "修改版 (Hack)",
+            TagFlag::BadDump => "损坏 dump [b]",
+        }
+    }
+}
+
+// 从名称中解析出的结构化信息
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct NameTags {
+    pub regions: Vec<String>,
+    pub languages: Vec<String>,
+    // 原样保留的修订号标签，如 "Rev 2"、"v1.1"
+    pub revision: Option<String>,
+    pub flags: Vec<TagFlag>,
+}
+
+// No-Intro 使用的地区名
+const REGIONS: &[&str] = &[
+    "World",
+    "USA",
+    "Europe",
+    "Japan",
+    "Asia",
+    "Australia",
+    "Brazil",
+    "Canada",
+    "China",
+    "France",
+    "Germany",
+    "Hong Kong",
+    "Italy",
+    "Korea",
+    "Netherlands",
+    "Spain",
+    "Sweden",
+    "Taiwan",
+    "UK",
+    "Russia",
+    "Scandinavia",
+    "Greece",
+    "Finland",
+    "Norway",
+    "Denmark",
+    "Portugal",
+    "Poland",
+    "Latin America",
+    "India",
+    "Mexico",
+    "Argentina",
+    "Belgium",
+    "Switzerland",
+    "Austria",
+    "Unknown",
+];
+
+// 名称中圆括号和方括号内的标签，如 "Tetris (World) (Rev 1) [b]" -> [('(', "World"), ('(', "Rev 1"), ('[', "b")]
+fn raw_tags(name: &str) -> Vec<(char, &str)> {
+    let mut tags = Vec::new();
+    let mut rest = name;
+    while let Some(start) = rest.find(['(', '[']) {
+        let (open, close) = if rest.as_bytes()[start] == b'(' {
+            ('(', ')')
+        } else {
+            ('[', ']')
+        };
+        let Some(len) = rest[start + 1..].find(close) else {
+            break;
+        };
+        tags.push((open, rest[start + 1..start + 1 + len].trim()));
+        rest = &rest[start + 1 + len + 1..];
+    }
+    tags
+}
+
+// 语言代码：En、Ja、Zh-Hant
+fn is_language_code(code: &str) -> bool {
+    let base = code.split('-').next().unwrap_or(code).as_bytes();
+    base.len() == 2 && base[0].is_ascii_uppercase() && base[1].is_ascii_lowercase()
+}
+
+fn split_parts(tag: &str, sep: &[char]) -> Vec<String> {
+    tag.split(sep).map(|p| p.trim().to_string()).collect()
+}
+
+// 方括号标记：字母后面可以跟编号或说明，如 [b]、[b1]、[h2 Trainer]
+fn is_dump_mark(tag: &str, mark: char) -> bool {
+    let mut chars = tag.chars();
+    if chars.next() != Some(mark) {
+        return false;
+    }
+    match chars.next() {
+        None => true,
+        Some(c) => c.is_ascii_digit() || c == ' ',
+    }
+}
+
+impl NameTags {
+    pub fn parse(name: &str) -> Self {
+        let mut tags = NameTags::default();
+        let mut flag = |f: TagFlag| {
+            if !tags.flags.contains(&f) {
+                tags.flags.push(f);
+            }
+        };
+        let mut regions = Vec::new();
+        let mut languages = Vec::new();
+        let mut revision = None;
+        for (open, tag) in raw_tags(name) {
+            if open == '[' {
+                if is_dump_mark(tag, 'b') {
+                    flag(TagFlag::BadDump);
+                } else if is_dump_mark(tag, 'h') {
+                    flag(TagFlag::Hack);
+                } else if is_dump_mark(tag, 'p') {
+                    flag(TagFlag::Pirate);
+                }
+                continue;
+            }
+            let word = tag.split(' ').next().unwrap_or(tag);
+            match word {
+                "Beta" => flag(TagFlag::Beta),
+                "Proto" | "Prototype" => flag(TagFlag::Proto),
+                "Demo" | "Sample" | "Kiosk" => flag(TagFlag::Demo),
+                "Unl" => flag(TagFlag::Unlicensed),
+                "Pirate" => flag(TagFlag::Pirate),
+                "Hack" => flag(TagFlag::Hack),
+                "Rev" if revision.is_none() => revision = Some(tag.to_string()),
+                _ if revision.is_none()
+                    && tag.starts_with('v')
+                    && tag[1..].starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    revision = Some(tag.to_string())
+                }
+                _ => {
+                    let parts = split_parts(tag, &[',']);
+                    if regions.is_empty() && parts.iter().all(|p| REGIONS.contains(&p.as_str())) {
+                        regions = parts;
+                    } else if languages.is_empty() {
+                        let parts = split_parts(tag, &[',', '+']);
+                        if parts.iter().all(|p| is_language_code(p)) {
+                            languages = parts;
+                        }
+                    }
+                }
+            }
+        }
+        tags.regions = regions;
+        tags.languages = languages;
+        tags.revision = revision;
+        tags
+    }
+
+    pub fn has_any(&self, flags: &[TagFlag]) -> bool {
+        self.flags.iter().any(|f| flags.contains(f))
+    }
+
+    // 修订号的排序键，数值大的较新；没有修订号时为空（视为最早的版本）
+    pub fn revision_key(&self) -> Vec<u32> {
+        let Some(rev) = &self.revision else {
+            return Vec::new();
+        };
+        let text = rev
+            .strip_prefix("Rev")
+            .or_else(|| rev.strip_prefix('v'))
+            .unwrap_or(rev)
+            .trim();
+        // 字母修订号 A、B… 依次为 1、2…
+        if let [c] = text.as_bytes() {
+            if c.is_ascii_alphabetic() {
+                return vec![(c.to_ascii_uppercase() - b'A') as u32 + 1];
+            }
+        }
+        text.split('.')
+            .map(|p| p.trim().parse().unwrap_or(0))
+            .collect()
+    }
+}
+
+// 去掉标签后的标题（小写），用于把不同地区的同名游戏归为一组；
+// 开头的方括号标签（如 "[BIOS] PlayStation (USA)"）算作标题的一部分
+pub fn base_title(name: &str) -> String {
+    let mut start = 0;
+    while name[start..].starts_with('[') {
+        match name[start..].find(']') {
+            Some(close) => start += close + 1,
+            None => break,
+        }
+        start += name[start..].len() - name[start..].trim_start().len();
+    }
+    let end = name[start..]
+        .find(['(', '['])
+        .map_or(name.len(), |i| start + i);
+    name[..end].trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (名称, 地区, 语言, 修订号, 标记)
+    type Case = (
+        &'static str,
+        &'static [&'static str],
+        &'static [&'static str],
+        Option<&'static str>,
+        &'static [TagFlag],
+    );
+
+    #[test]
+    fn parses_no_intro_and_redump_tags() {
+        use TagFlag::*;
+        let cases: &[Case] = &[
+            (
+                "Tetris (World) (Rev 1)",
+                &["World"],
+                &[],
+                Some("Rev 1"),
+                &[],
+            ),
+            (
+                "Legend of Zelda, The - A Link to the Past (Europe) (En,Fr,De)",
+                &["Europe"],
+                &["En", "Fr", "De"],
+                None,
+                &[],
+            ),
+            (
+                "Pokemon - Red Version (USA, Europe) (SGB Enhanced)",
+                &["USA", "Europe"],
+                &[],
+                None,
+                &[],
+            ),
+            (
+                "Street Fighter II (Japan) (Rev A)",
+                &["Japan"],
+                &[],
+                Some("Rev A"),
+                &[],
+            ),
+            (
+                "Mortal Kombat (Europe) (v1.1)",
+                &["Europe"],
+                &[],
+                Some("v1.1"),
+                &[],
+            ),
+            ("Final Fantasy VII (USA) (Disc 1)", &["USA"], &[], None, &[]),
+            (
+                "Sonic the Hedgehog (USA) (Beta 2)",
+                &["USA"],
+                &[],
+                None,
+                &[Beta],
+            ),
+            (
+                "Star Fox 2 (Japan) (Proto) (Beta)",
+                &["Japan"],
+                &[],
+                None,
+                &[Proto, Beta],
+            ),
+            (
+                "Kirby's Adventure (USA) (Demo) (Kiosk)",
+                &["USA"],
+                &[],
+                None,
+                &[Demo],
+            ),
+            ("Action 52 (USA) (Unl)", &["USA"], &[], None, &[Unlicensed]),
+            (
+                "Super Mario World (China) (Pirate)",
+                &["China"],
+                &[],
+                None,
+                &[Pirate],
+            ),
+            (
+                "Tetris (Japan) (En) [b]",
+                &["Japan"],
+                &["En"],
+                None,
+                &[BadDump],
+            ),
+            (
+                "Metroid (USA) [b1][h2 Trainer]",
+                &["USA"],
+                &[],
+                None,
+                &[BadDump, Hack],
+            ),
+            // [T+Eng] 是翻译补丁，不是 [t] 之类的标记；[bios] 也不是损坏标记
+            ("Mother (Japan) [T+Eng]", &["Japan"], &[], None, &[]),
+            (
+                "[BIOS] PlayStation (USA) (v3.0)",
+                &["USA"],
+                &[],
+                Some("v3.0"),
+                &[],
+            ),
+            (
+                "Sangokushi (Hong Kong, Taiwan) (Zh-Hant,Ja)",
+                &["Hong Kong", "Taiwan"],
+                &["Zh-Hant", "Ja"],
+                None,
+                &[],
+            ),
+        ];
+        for &(name, regions, languages, revision, flags) in cases {
+            let tags = NameTags::parse(name);
+            assert_eq!(tags.regions, regions, "{}", name);
+            assert_eq!(tags.languages, languages, "{}", name);
+            assert_eq!(tags.revision.as_deref(), revision, "{}", name);
+            assert_eq!(tags.flags, flags, "{}", name);
+        }
+    }
+
+    #[test]
+    fn orders_revisions() {
+        let key = |name: &str| NameTags::parse(name).revision_key();
+        let cases: &[(&str, &[u32])] = &[
+            ("Tetris (World)", &[]),
+            ("Tetris (World) (Rev 1)", &[1]),
+            ("Tetris (World) (Rev 2)", &[2]),
+            ("Street Fighter II (Japan) (Rev A)", &[1]),
+            ("Street Fighter II (Japan) (Rev B)", &[2]),
+            ("Mortal Kombat (Europe) (v1.1)", &[1, 1]),
+            ("Mortal Kombat (Europe) (v1.10)", &[1, 10]),
+        ];
+        for &(name, expected) in cases {
+            assert_eq!(key(name), expected, "{}", name);
+        }
+        assert!(key("Tetris (World)") < key("Tetris (World) (Rev 1)"));
+        assert!(key("Mortal Kombat (Europe) (v1.1)") < key("Mortal Kombat (Europe) (v1.10)"));
+    }
+
+    #[test]
+    fn strips_tags_from_titles() {
+        let cases = [
+            ("Tetris (World) (Rev 1)", "tetris"),
+            (
+                "Legend of Zelda, The - A Link to the Past (Europe) (En,Fr,De)",
+                "legend of zelda, the - a link to the past",
+            ),
+            ("Metroid (USA) [b1]", "metroid"),
+            ("Metroid [b]", "metroid"),
+            ("[BIOS] PlayStation (USA) (v3.0)", "[bios] playstation"),
+            ("No Tags", "no tags"),
+        ];
+        for (name, title) in cases {
+            assert_eq!(base_title(name), title, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use sha1::{Digest, Sha1};
+use walkdir::WalkDir;
+
+use crate::xml::{GameEntry, RomEntry};
+
+// 单个文件（或 zip 成员）的校验值
+#[derive(Debug, Clone)]
+pub struct FileHashes {
+    pub member: Option<String>,
+    pub size: u64,
+    pub crc: String,
+    // zip 成员只读取目录中的 CRC32 时没有 SHA1
+    pub sha1: Option<String>,
+}
+
+pub fn hash_reader<R: Read>(mut reader: R) -> std::io::Result<FileHashes> {
+    let mut crc = crc32fast::Hasher::new();
+    let mut sha1 = Sha1::new();
+    let mut size: u64 = 0;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        crc.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok(FileHashes {
+        member: None,
+        size,
+        crc: format!("{:08x}", crc.finalize()),
+        sha1: Some(format!("{:x}", sha1.finalize())),
+    })
+}
+
+pub fn hash_file(path: &Path) -> Result<FileHashes> {
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    hash_reader(file).with_context(|| format!("读取文件失败: {}", path.display()))
+}
+
+pub fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.eq_ignore_ascii_case("zip"))
+        .unwrap_or(false)
+}
+
+// 识别一个文件：普通文件整体计算校验值；zip 文件不解压，直接读取每个成员记录的 CRC32，
+// 只有 is_known 判定为未匹配时才解压该成员计算完整校验值
+pub fn identify_file<F>(path: &Path, is_known: F) -> Result<Vec<FileHashes>>
+where
+    F: Fn(&FileHashes) -> bool,
+{
+    if !is_zip(path) {
+        return Ok(vec![hash_file(path)?]);
+    }
+
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    let mut archive = match zip::ZipArchive::new(file) {
+        Ok(a) => a,
+        // 扩展名是 zip 但不是有效的压缩包，按普通文件处理
+        Err(_) => return Ok(vec![hash_file(path)?]),
+    };
+
+    let mut results = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .with_context(|| format!("读取压缩包成员失败: {}", path.display()))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let member = entry.name().to_string();
+        let stored = FileHashes {
+            member: Some(member.clone()),
+            size: entry.size(),
+            crc: format!("{:08x}", entry.crc32()),
+            sha1: None,
+        };
+        if is_known(&stored) {
+            results.push(stored);
+            continue;
+        }
+        let mut hashed = hash_reader(&mut entry)
+            .with_context(|| format!("解压失败: {} ({})", path.display(), member))?;
+        hashed.member = Some(member);
+        results.push(hashed);
+    }
+    Ok(results)
+}
+
+pub fn rom_matches(rom: &RomEntry, h: &FileHashes) -> bool {
+    if let (Some(a), Some(b)) = (&rom.sha1, &h.sha1) {
+        return a == b;
+    }
+    match &rom.crc {
+        Some(crc) => crc == &h.crc && rom.size.map(|s| s == h.size).unwrap_or(true),
+        None => false,
+    }
+}
+
+// (游戏序号, ROM 序号)
+type RomRef = (usize, usize);
+
+struct LookupGame {
+    platform: String,
+    name: String,
+    rom_names: Vec<String>,
+}
+
+// 从索引构建的校验值查找表，可以脱离索引移动到后台线程
+pub struct RomLookup {
+    games: Vec<LookupGame>,
+    by_sha1: HashMap<String, Vec<RomRef>>,
+    by_crc: HashMap<(String, Option<u64>), Vec<RomRef>>,
+}
+
+impl RomLookup {
+    pub fn build(index: &[GameEntry]) -> Self {
+        let mut games = Vec::new();
+        let mut by_sha1: HashMap<String, Vec<RomRef>> = HashMap::new();
+        let mut by_crc: HashMap<(String, Option<u64>), Vec<RomRef>> = HashMap::new();
+        for g in index.iter().filter(|g| !g.roms.is_empty()) {
+            let gi = games.len();
+            for (ri, rom) in g.roms.iter().enumerate() {
+                if let Some(sha1) = &rom.sha1 {
+                    by_sha1.entry(sha1.clone()).or_default().push((gi, ri));
+                }
+                if let Some(crc) = &rom.crc {
+                    by_crc
+                        .entry((crc.clone(), rom.size))
+                        .or_default()
+                        .push((gi, ri));
+                }
+            }
+            games.push(LookupGame {
+                platform: g.platform.clone(),
+                name: g.name.clone(),
+                rom_names: g.roms.iter().map(|r| r.name.clone()).collect(),
+            });
+        }
+        Self {
+            games,
+            by_sha1,
+            by_crc,
+        }
+    }
+
+    // 优先用 SHA1 匹配，其次用 CRC32 + 大小
+    fn find(&self, h: &FileHashes) -> &[RomRef] {
+        if let Some(refs) = h.sha1.as_ref().and_then(|s| self.by_sha1.get(s)) {
+            return refs;
+        }
+        if let Some(refs) = self.by_crc.get(&(h.crc.clone(), Some(h.size))) {
+            return refs;
+        }
+        if let Some(refs) = self.by_crc.get(&(h.crc.clone(), None)) {
+            return refs;
+        }
+        &[]
+    }
+
+    // 返回匹配到的 (游戏名, ROM 文件名)
+    pub fn identify(&self, h: &FileHashes) -> Option<(&str, &str)> {
+        let (gi, ri) = *self.find(h).first()?;
+        let g = &self.games[gi];
+        Some((g.name.as_str(), g.rom_names[ri].as_str()))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PlatformReport {
+    pub platform: String,
+    pub have: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ScanReport {
+    pub root: PathBuf,
+    pub scanned: usize,
+    pub platforms: Vec<PlatformReport>,
+    // zip 内未识别的成员以 "压缩包路径/成员名" 的形式列出
+    pub unknown: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+// 扫描进度（后台线程更新，界面读取）
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    pub done: AtomicUsize,
+    pub total: AtomicUsize,
+}
+
+pub fn scan_collection(root: &Path, lookup: &RomLookup, progress: &ScanProgress) -> ScanReport {
+    let files: Vec<PathBuf> = WalkDir::new(root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    progress.total.store(files.len(), Ordering::Relaxed);
+
+    let hashed: Vec<(PathBuf, Result<Vec<FileHashes>>)> = files
+        .into_par_iter()
+        .map(|p| {
+            let h = identify_file(&p, |h| !lookup.find(h).is_empty());
+            progress.done.fetch_add(1, Ordering::Relaxed);
+            (p, h)
+        })
+        .collect();
+
+    let mut report = ScanReport {
+        root: root.to_path_buf(),
+        scanned: hashed.len(),
+        ..Default::default()
+    };
+    let mut found: HashSet<RomRef> = HashSet::new();
+    for (path, h) in hashed {
+        match h {
+            Ok(list) => {
+                for h in list {
+                    let refs = lookup.find(&h);
+                    if !refs.is_empty() {
+                        found.extend(refs.iter().copied());
+                    } else if let Some(member) = &h.member {
+                        report.unknown.push(path.join(member));
+                    } else {
+                        report.unknown.push(path.clone());
+                    }
+                }
+            }
+            Err(e) => report.errors.push(format!("{:#}", e)),
+        }
+    }
+
+    // 只统计至少命中过一个文件的平台，避免把整个数据库都列为缺失
+    let mut found_per_game: HashMap<usize, usize> = HashMap::new();
+    for (gi, _) in &found {
+        *found_per_game.entry(*gi).or_default() += 1;
+    }
+    let hit_platforms: HashSet<&str> = found_per_game
+        .keys()
+        .map(|gi| lookup.games[*gi].platform.as_str())
+        .collect();
+
+    let mut per_platform: BTreeMap<&str, PlatformReport> = BTreeMap::new();
+    for (gi, g) in lookup.games.iter().enumerate() {
+        if !hit_platforms.contains(g.platform.as_str()) {
+            continue;
+        }
+        let entry = per_platform
+            .entry(g.platform.as_str())
+            .or_insert_with(|| PlatformReport {
+                platform: g.platform.clone(),
+                ..Default::default()
+            });
+        // 所有 ROM 都找到才算已有
+        if found_per_game.get(&gi).copied().unwrap_or(0) >= g.rom_names.len() {
+            entry.have.push(g.name.clone());
+        } else {
+            entry.missing.push(g.name.clone());
+        }
+    }
+    report.platforms = per_platform.into_values().collect();
+    report.unknown.sort();
+    report
+}
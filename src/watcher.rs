@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+use anyhow::{Context, Result};
+use eframe::egui;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::dat::is_dat_file;
+
+// 监视 DAT 库目录，收集新增、修改和删除的 DAT 文件及目录
+pub struct DatWatcher {
+    // 需要持有 watcher，drop 后监视即停止
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<notify::Event>>,
+}
+
+impl DatWatcher {
+    pub fn new(dirs: &[PathBuf], ctx: egui::Context) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+            ctx.request_repaint();
+        })
+        .context("无法创建文件监视器")?;
+        // 单个目录无法监视时不影响其他目录
+        for dir in dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::Recursive) {
+                eprintln!("无法监视目录 {}: {}", dir.display(), e);
+            }
+        }
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    // 取出自上次调用以来发生变化的 DAT 文件，以及新建、删除或改名的目录
+    pub fn changed_files(&self) -> HashSet<PathBuf> {
+        let mut changed = HashSet::new();
+        for event in self.rx.try_iter().flatten() {
+            // 删除或移走后路径已不存在，无法判断原来是不是目录，一并交给重载线程处理
+            let moved = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            );
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            changed.extend(
+                event
+                    .paths
+                    .into_iter()
+                    .filter(|p| is_dat_file(p) || (moved && (p.is_dir() || !p.exists()))),
+            );
+        }
+        changed
+    }
+}
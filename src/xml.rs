@@ -1,262 +1,582 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::ops::Range;
 use std::path::Path;
 
 use anyhow::{Context, Result};
-use quick_xml::events::Event;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::name::QName;
 use quick_xml::Reader;
 
-#[derive(Debug, Clone)]
+use crate::dat::{DatHeader, DatSource};
+use crate::normalize::Romanized;
+use crate::tags::NameTags;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GameEntry {
     pub platform: String,
     pub name: String,
     pub archive_name: Option<String>,
     pub region: Option<String>,
     pub languages: Option<String>,
+    // Logiqx 的 <description>/<year>/<manufacturer>，No-Intro 的 DAT 中没有
+    pub description: Option<String>,
+    pub year: Option<String>,
+    pub manufacturer: Option<String>,
+    // 父版本与共享 ROM 的来源（cloneof/romof）
+    pub clone_of: Option<String>,
+    pub rom_of: Option<String>,
+    // MAME 的 <driver status>：good/imperfect/preliminary
+    pub driver_status: Option<String>,
+    // 由名称中的标签解析出的地区、语言、修订号和标记，加载时统一填写
+    pub tags: NameTags,
+    // 中日文名称的拼音/罗马字，加载时填写
+    pub romanized: Option<Romanized>,
+    pub roms: Vec<RomEntry>,
+    // MAME 的 <disk>（CHD），只有名称和校验值，不参与收藏校验
+    pub disks: Vec<RomEntry>,
+    // 条目及其子元素的全部属性和文本，按元素分组
+    pub fields: Vec<FieldSection>,
+    // 所属 DAT 库名称，由加载器填写
+    pub library: String,
     pub file_path: String,
-    pub game_idx: usize,
+    // 压缩包中的 DAT 成员名，普通文件为 None
+    pub dat_member: Option<String>,
+    // 条目在（解压后的）DAT 中的字节范围，用于直接读取原始片段
+    pub source_span: Range<u64>,
+}
+
+// 单个 ROM 记录（Logiqx 的 <rom> 或 No-Intro 的 <file>）
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RomEntry {
+    pub name: String,
+    pub extension: Option<String>,
+    pub size: Option<u64>,
+    pub crc: Option<String>,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+}
+
+// 一个元素的属性与子元素文本，name 为相对于条目的路径（如 "source/details"）
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct FieldSection {
+    pub name: String,
+    pub values: Vec<(String, String)>,
+}
+
+// 不收集到 fields 中的元素：ROM 已单独列出，其余是 MAME 中数量庞大的硬件描述
+const SKIPPED_SECTIONS: &[&[u8]] = &[
+    b"rom",
+    b"file",
+    b"disk",
+    b"device_ref",
+    b"sample",
+    b"chip",
+    b"dipswitch",
+    b"configuration",
+    b"port",
+    b"slot",
+    b"adjuster",
+    b"biosset",
+    b"ramoption",
+    b"softwarelist",
+    b"device",
+];
+
+struct FieldFrame {
+    name: String,
+    path: String,
+    section: Option<usize>,
+}
+
+// 收集游戏元素及其所有子元素的属性和文本
+#[derive(Default)]
+struct FieldCollector {
+    stack: Vec<FieldFrame>,
+    // 处于被跳过的元素内部时的嵌套深度
+    skip_depth: usize,
+    sections: Vec<FieldSection>,
 }
 
-pub fn parse_games_from_file(path: &Path) -> Result<Vec<GameEntry>> {
-    let platform = infer_platform_from_filename(path).unwrap_or_else(|| "Unknown".to_string());
+impl FieldCollector {
+    fn section_of(&mut self, frame: usize) -> usize {
+        if let Some(i) = self.stack[frame].section {
+            return i;
+        }
+        self.sections.push(FieldSection {
+            name: self.stack[frame].path.clone(),
+            values: Vec::new(),
+        });
+        let i = self.sections.len() - 1;
+        self.stack[frame].section = Some(i);
+        i
+    }
+
+    fn open<R>(&mut self, reader: &Reader<R>, e: &BytesStart, empty: bool) {
+        if self.skip_depth > 0 || SKIPPED_SECTIONS.contains(&e.name().as_ref()) {
+            if !empty {
+                self.skip_depth += 1;
+            }
+            return;
+        }
+        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+        // 条目本身以元素名作为分组名，子元素使用相对路径
+        let path = match self.stack.len() {
+            0 | 1 => name.clone(),
+            _ => format!("{}/{}", self.stack[self.stack.len() - 1].path, name),
+        };
+        self.stack.push(FieldFrame {
+            name,
+            path,
+            section: None,
+        });
+        let frame = self.stack.len() - 1;
+        for a in e.attributes().flatten() {
+            let key = String::from_utf8_lossy(a.key.as_ref()).into_owned();
+            let value = attr_value(reader, &a).unwrap_or_default();
+            let i = self.section_of(frame);
+            self.sections[i].values.push((key, value));
+        }
+        if empty {
+            self.stack.pop();
+        }
+    }
+
+    // 元素的文本记在父元素的分组中，键为元素名
+    fn text(&mut self, text: String) {
+        if self.skip_depth > 0 || self.stack.is_empty() || text.is_empty() {
+            return;
+        }
+        let frame = self.stack.len() - 1;
+        let (target, key) = if frame == 0 {
+            (0, "text".to_string())
+        } else {
+            (frame - 1, self.stack[frame].name.clone())
+        };
+        let i = self.section_of(target);
+        self.sections[i].values.push((key, text));
+    }
+
+    fn close(&mut self) {
+        if self.skip_depth > 0 {
+            self.skip_depth -= 1;
+        } else {
+            self.stack.pop();
+        }
+    }
+
+    fn take(&mut self) -> Vec<FieldSection> {
+        self.stack.clear();
+        self.skip_depth = 0;
+        std::mem::take(&mut self.sections)
+    }
+}
+
+// MAME -listxml（根元素为 <mame>）中的条目统一归入此平台
+pub const ARCADE_PLATFORM: &str = "Arcade";
+
+// 解析出错时的字节位置，作为错误链的一环，可通过 downcast_ref 取回
+#[derive(Debug, Clone, Copy)]
+pub struct ParsePosition(pub usize);
 
-    let mut reader =
-        Reader::from_file(path).with_context(|| format!("读取 XML 失败: {}", path.display()))?;
+impl std::fmt::Display for ParsePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "字节位置 {}", self.0)
+    }
+}
+
+// 游戏条目下需要读取文本内容的子元素
+#[derive(Debug, Clone, Copy)]
+enum TextField {
+    Description,
+    Year,
+    Manufacturer,
+}
+
+impl TextField {
+    fn of(name: QName) -> Option<Self> {
+        match name {
+            QName(b"description") => Some(Self::Description),
+            QName(b"year") => Some(Self::Year),
+            QName(b"manufacturer") => Some(Self::Manufacturer),
+            _ => None,
+        }
+    }
+}
+
+// Logiqx 的 DAT 使用 <game>，MAME 风格的使用 <machine>
+fn is_game_tag(name: QName) -> bool {
+    name == QName(b"game") || name == QName(b"machine")
+}
+
+pub fn parse_games<R: BufRead>(
+    input: R,
+    source: &DatSource,
+) -> Result<(DatHeader, Vec<GameEntry>)> {
+    let mut header = DatHeader::default();
+    let mut platform = source.platform(&header);
+
+    let mut reader = Reader::from_reader(input);
     reader.trim_text(true);
 
     let mut buf = Vec::new();
 
+    let mut in_header = false;
+    let mut header_key: Option<String> = None;
     let mut in_game = false;
     let mut current_game_name: Option<String> = None;
     let mut current_archive_region: Option<String> = None;
     let mut current_archive_languages: Option<String> = None;
     let mut current_archive_name: Option<String> = None;
+    let mut current_archive_number: Option<String> = None;
+    let mut current_archive_clone: Option<String> = None;
+    let mut current_game_id: Option<String> = None;
+    let mut current_clone_of_id: Option<String> = None;
     let mut current_game_region: Option<String> = None;
     let mut current_game_languages: Option<String> = None;
     let mut current_details_region: Option<String> = None;
-    let mut game_idx_counter: usize = 0;
+    let mut current_description: Option<String> = None;
+    let mut current_year: Option<String> = None;
+    let mut current_manufacturer: Option<String> = None;
+    let mut current_clone_of: Option<String> = None;
+    let mut current_rom_of: Option<String> = None;
+    let mut current_driver_status: Option<String> = None;
+    let mut current_disks: Vec<RomEntry> = Vec::new();
+    // MAME 的设备（isdevice）和不可运行的条目不是游戏，跳过
+    let mut skip_game = false;
+    let mut current_start: u64 = 0;
+    let mut current_text: Option<TextField> = None;
+    let mut current_roms: Vec<RomEntry> = Vec::new();
 
     let mut results: Vec<GameEntry> = Vec::new();
+    // No-Intro 的 <archive number=".." clone=".."> 以编号指向父版本，读完全部条目后再换算成名称
+    let mut archive_numbers: HashMap<String, String> = HashMap::new();
+    let mut archive_clones: Vec<(usize, String)> = Vec::new();
+    // 新版 No-Intro 标准 DAT 的 <game id=".." cloneofid="..">，同样读完后换算
+    let mut game_ids: HashMap<String, String> = HashMap::new();
+    let mut id_clones: Vec<(usize, String)> = Vec::new();
+
+    let mut fields = FieldCollector::default();
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => match e.name() {
-                QName(b"game") => {
-                    in_game = true;
-                    for a in e.attributes().flatten() {
-                        if a.key == QName(b"name") {
-                            current_game_name = a
-                                .decode_and_unescape_value(&reader)
-                                .ok()
-                                .map(|c| c.into_owned());
-                        } else if a.key == QName(b"region") {
-                            current_game_region = a
-                                .decode_and_unescape_value(&reader)
-                                .ok()
-                                .map(|c| c.into_owned());
-                        } else if a.key == QName(b"languages") {
-                            current_game_languages = a
-                                .decode_and_unescape_value(&reader)
-                                .ok()
-                                .map(|c| c.into_owned());
+        let event = reader.read_event_into(&mut buf);
+        // 在按字段处理之前，先把条目内的所有事件交给 FieldCollector
+        match &event {
+            Ok(Event::Start(e)) if !in_game && is_game_tag(e.name()) => {
+                // 上一个条目可能被跳过，先丢弃残留的数据
+                fields.take();
+                fields.open(&reader, e, false);
+            }
+            Ok(Event::Start(e)) if in_game => fields.open(&reader, e, false),
+            Ok(Event::Empty(e)) if in_game => fields.open(&reader, e, true),
+            Ok(Event::Text(t)) if in_game => {
+                if let Ok(text) = t.unescape() {
+                    fields.text(text.into_owned());
+                }
+            }
+            Ok(Event::End(_)) if in_game => fields.close(),
+            _ => {}
+        }
+        match event {
+            // -listxml 没有 <header>，以 build 属性作为版本
+            Ok(Event::Start(e)) if e.name() == QName(b"mame") => {
+                platform = ARCADE_PLATFORM.to_string();
+                header.set("name", "MAME".to_string());
+                for a in e.attributes().flatten() {
+                    if a.key == QName(b"build") {
+                        header.version = attr_value(&reader, &a);
+                    }
+                }
+            }
+            // <header> 位于所有游戏之前，读完后即可确定平台名称
+            Ok(Event::Start(e)) if !in_game && e.name() == QName(b"header") => {
+                in_header = true;
+            }
+            Ok(Event::Start(e)) if in_header => {
+                header_key = Some(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+            }
+            Ok(Event::Text(t)) if in_header => {
+                if let (Some(key), Ok(text)) = (&header_key, t.unescape()) {
+                    header.set(key, text.into_owned());
+                }
+            }
+            Ok(Event::End(e)) if in_header => {
+                if e.name() == QName(b"header") {
+                    in_header = false;
+                    platform = source.platform(&header);
+                }
+                header_key = None;
+            }
+            Ok(Event::Start(e)) if is_game_tag(e.name()) => {
+                in_game = true;
+                skip_game = false;
+                // 读完开始标签后位置在 '>' 之后，减去 "<...>" 的长度即为起点
+                current_start = (reader.buffer_position() - e.len() - 2) as u64;
+                // 上一个条目被跳过或没有名称时不会被取走，这里重置全部逐条目的状态
+                current_game_name = None;
+                current_game_region = None;
+                current_game_languages = None;
+                current_clone_of = None;
+                current_rom_of = None;
+                current_driver_status = None;
+                current_archive_region = None;
+                current_archive_languages = None;
+                current_archive_name = None;
+                current_archive_number = None;
+                current_archive_clone = None;
+                current_game_id = None;
+                current_clone_of_id = None;
+                current_details_region = None;
+                current_description = None;
+                current_year = None;
+                current_manufacturer = None;
+                current_text = None;
+                current_disks.clear();
+                current_roms.clear();
+                for a in e.attributes().flatten() {
+                    match a.key {
+                        QName(b"name") => current_game_name = attr_value(&reader, &a),
+                        QName(b"region") => current_game_region = attr_value(&reader, &a),
+                        QName(b"languages") => current_game_languages = attr_value(&reader, &a),
+                        QName(b"cloneof") => current_clone_of = attr_value(&reader, &a),
+                        QName(b"id") => current_game_id = attr_value(&reader, &a),
+                        QName(b"cloneofid") => current_clone_of_id = attr_value(&reader, &a),
+                        QName(b"romof") => current_rom_of = attr_value(&reader, &a),
+                        QName(b"isdevice") => {
+                            skip_game |= attr_value(&reader, &a).as_deref() == Some("yes")
+                        }
+                        QName(b"runnable") => {
+                            skip_game |= attr_value(&reader, &a).as_deref() == Some("no")
                         }
+                        _ => {}
                     }
-                    current_archive_region = None;
-                    current_archive_languages = None;
-                    current_archive_name = None;
-                    current_details_region = None;
                 }
-                n if n == QName(b"archive") && in_game => {
+            }
+            Ok(Event::Start(e)) if in_game && TextField::of(e.name()).is_some() => {
+                current_text = TextField::of(e.name());
+            }
+            // <archive>/<details>/<rom>/<file> 可能是自闭合标签，也可能带子节点
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if in_game => match e.name() {
+                QName(b"archive") => {
                     for a in e.attributes().flatten() {
                         if a.key == QName(b"region") {
-                            current_archive_region = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
+                            current_archive_region =
+                                Some(attr_value(&reader, &a).unwrap_or_default());
                         } else if a.key == QName(b"languages") {
-                            current_archive_languages = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
+                            current_archive_languages =
+                                Some(attr_value(&reader, &a).unwrap_or_default());
                         } else if a.key == QName(b"name") {
-                            current_archive_name = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
+                            current_archive_name =
+                                Some(attr_value(&reader, &a).unwrap_or_default());
+                        } else if a.key == QName(b"number") {
+                            current_archive_number = attr_value(&reader, &a);
+                        } else if a.key == QName(b"clone") {
+                            current_archive_clone = attr_value(&reader, &a);
                         }
                     }
                 }
-                QName(b"details") if in_game => {
-                    if current_archive_region.is_none() && current_game_region.is_none() {
-                        for a in e.attributes().flatten() {
-                            if a.key == QName(b"region") {
-                                current_details_region = a
-                                    .decode_and_unescape_value(&reader)
-                                    .ok()
-                                    .map(|c| c.into_owned());
-                            }
+                QName(b"details")
+                    if current_archive_region.is_none() && current_game_region.is_none() =>
+                {
+                    for a in e.attributes().flatten() {
+                        if a.key == QName(b"region") {
+                            current_details_region = attr_value(&reader, &a);
                         }
                     }
                 }
-                _ => {}
-            },
-            Ok(Event::Empty(e)) => match e.name() {
-                QName(b"archive") if in_game => {
+                QName(b"driver") => {
                     for a in e.attributes().flatten() {
-                        if a.key == QName(b"region") {
-                            current_archive_region = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
-                        } else if a.key == QName(b"languages") {
-                            current_archive_languages = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
-                        } else if a.key == QName(b"name") {
-                            current_archive_name = Some(
-                                a.decode_and_unescape_value(&reader)
-                                    .map(|c| c.into_owned())
-                                    .unwrap_or_default(),
-                            );
+                        if a.key == QName(b"status") {
+                            current_driver_status = attr_value(&reader, &a);
                         }
                     }
                 }
-                QName(b"details") if in_game => {
-                    if current_archive_region.is_none() && current_game_region.is_none() {
-                        for a in e.attributes().flatten() {
-                            if a.key == QName(b"region") {
-                                current_details_region = a
-                                    .decode_and_unescape_value(&reader)
-                                    .ok()
-                                    .map(|c| c.into_owned());
+                QName(b"rom") | QName(b"file") | QName(b"disk") => {
+                    let mut rom = RomEntry::default();
+                    for a in e.attributes().flatten() {
+                        match a.key {
+                            QName(b"name") | QName(b"forcename") => {
+                                if let Some(v) = attr_value(&reader, &a).filter(|v| !v.is_empty()) {
+                                    rom.name = v;
+                                }
+                            }
+                            QName(b"extension") => rom.extension = attr_value(&reader, &a),
+                            QName(b"size") => {
+                                rom.size = attr_value(&reader, &a).and_then(|v| v.parse().ok())
+                            }
+                            QName(b"crc") | QName(b"crc32") => {
+                                rom.crc = attr_value(&reader, &a).map(|v| v.to_lowercase())
+                            }
+                            QName(b"md5") => {
+                                rom.md5 = attr_value(&reader, &a).map(|v| v.to_lowercase())
+                            }
+                            QName(b"sha1") => {
+                                rom.sha1 = attr_value(&reader, &a).map(|v| v.to_lowercase())
                             }
+                            _ => {}
                         }
                     }
+                    if e.name() == QName(b"disk") {
+                        current_disks.push(rom);
+                    } else {
+                        current_roms.push(rom);
+                    }
                 }
                 _ => {}
             },
-            Ok(Event::End(e)) => {
-                if e.name() == QName(b"game") {
-                    if let Some(name) = current_game_name.take() {
-                        let merged_region = current_archive_region
-                            .take()
-                            .or(current_game_region.take())
-                            .or(current_details_region.take());
-                        let merged_languages = current_archive_languages
-                            .take()
-                            .or(current_game_languages.take());
-
-                        results.push(GameEntry {
-                            platform: platform.clone(),
-                            name,
-                            archive_name: current_archive_name.take(),
-                            region: merged_region,
-                            languages: merged_languages,
-                            file_path: path.display().to_string(),
-                            game_idx: game_idx_counter,
-                        });
-                        game_idx_counter += 1;
+            Ok(Event::Text(t)) if current_text.is_some() => {
+                let text = t.unescape().map(|c| c.into_owned()).ok();
+                match current_text {
+                    Some(TextField::Description) => current_description = text,
+                    Some(TextField::Year) => current_year = text,
+                    Some(TextField::Manufacturer) => current_manufacturer = text,
+                    None => {}
+                }
+            }
+            Ok(Event::End(e)) if current_text.is_some() && !is_game_tag(e.name()) => {
+                current_text = None;
+            }
+            Ok(Event::End(e)) if is_game_tag(e.name()) => {
+                let name = current_game_name.take().filter(|_| !skip_game);
+                if let Some(name) = name {
+                    let merged_region = current_archive_region
+                        .take()
+                        .or(current_game_region.take())
+                        .or(current_details_region.take());
+                    let merged_languages = current_archive_languages
+                        .take()
+                        .or(current_game_languages.take());
+                    let archive_name = current_archive_name.take();
+
+                    // No-Intro 的 <file> 只有扩展名，文件名取归档名（或游戏名）
+                    let mut roms = std::mem::take(&mut current_roms);
+                    for rom in roms.iter_mut() {
+                        if rom.name.is_empty() {
+                            let stem = archive_name.as_deref().unwrap_or(&name);
+                            rom.name = match &rom.extension {
+                                Some(ext) if !ext.is_empty() => format!("{}.{}", stem, ext),
+                                _ => stem.to_string(),
+                            };
+                        }
                     }
-                    in_game = false;
+
+                    if let Some(number) = current_archive_number.take() {
+                        archive_numbers.insert(number, name.clone());
+                    }
+                    // clone="P" 表示自身就是父版本
+                    if let Some(parent) = current_archive_clone.take().filter(|c| c != "P") {
+                        archive_clones.push((results.len(), parent));
+                    }
+                    if let Some(id) = current_game_id.take() {
+                        game_ids.insert(id, name.clone());
+                    }
+                    if let Some(parent) = current_clone_of_id.take() {
+                        id_clones.push((results.len(), parent));
+                    }
+                    results.push(GameEntry {
+                        platform: platform.clone(),
+                        name,
+                        archive_name,
+                        region: merged_region,
+                        languages: merged_languages,
+                        description: current_description.take(),
+                        year: current_year.take(),
+                        manufacturer: current_manufacturer.take(),
+                        clone_of: current_clone_of.take(),
+                        rom_of: current_rom_of.take(),
+                        driver_status: current_driver_status.take(),
+                        tags: NameTags::default(),
+                        romanized: None,
+                        roms,
+                        disks: std::mem::take(&mut current_disks),
+                        fields: fields.take(),
+                        library: String::new(),
+                        file_path: source.path.display().to_string(),
+                        dat_member: source.member.map(str::to_string),
+                        source_span: current_start..reader.buffer_position() as u64,
+                    });
                 }
+                in_game = false;
+                current_text = None;
             }
             Ok(Event::Eof) => break,
             Err(err) => {
-                return Err(err).with_context(|| format!("解析失败: {}", path.display()));
+                return Err(anyhow::Error::new(err))
+                    .context(ParsePosition(reader.buffer_position()))
+                    .with_context(|| format!("解析失败: {}", source));
             }
             _ => {}
         }
     }
 
-    Ok(results)
+    for (i, number) in archive_clones {
+        let game = &mut results[i];
+        if game.clone_of.is_none() {
+            game.clone_of = archive_numbers.get(&number).cloned();
+        }
+    }
+    for (i, id) in id_clones {
+        let game = &mut results[i];
+        if game.clone_of.is_none() {
+            game.clone_of = game_ids.get(&id).cloned();
+        }
+    }
+
+    Ok((header, results))
+}
+
+fn attr_value<R>(reader: &Reader<R>, a: &Attribute) -> Option<String> {
+    a.decode_and_unescape_value(reader)
+        .ok()
+        .map(|c| c.into_owned())
 }
 
-fn infer_platform_from_filename(path: &Path) -> Option<String> {
+pub fn infer_platform_from_filename(path: &Path) -> Option<String> {
     let fname = path.file_stem()?.to_string_lossy();
     let s = fname.split(" (").next().unwrap_or(&fname);
     Some(s.to_string())
 }
 
-pub fn extract_game_xml_by_index(path: &Path, target_idx: usize) -> Result<String> {
-    use quick_xml::Writer;
-    use std::io::Cursor;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut reader =
-        Reader::from_file(path).with_context(|| format!("读取 XML 失败: {}", path.display()))?;
-    reader.trim_text(false);
-    let mut buf = Vec::new();
-
-    let mut idx: usize = 0;
-    let mut capturing = false;
-    let mut depth: i32 = 0;
-    let mut output: Vec<u8> = Vec::new();
-    let mut writer = Writer::new(Cursor::new(&mut output));
-
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
-                if e.name() == QName(b"game") {
-                    if idx == target_idx {
-                        capturing = true;
-                        depth = 1;
-                        writer.write_event(Event::Start(e.to_owned())).ok();
-                    } else {
-                        idx += 1;
-                    }
-                } else if capturing {
-                    depth += 1;
-                    writer.write_event(Event::Start(e.to_owned())).ok();
-                }
-            }
-            Ok(Event::Empty(e)) => {
-                if e.name() == QName(b"game") {
-                    if idx == target_idx {
-                        writer.write_event(Event::Empty(e.to_owned())).ok();
-                        break;
-                    } else {
-                        idx += 1;
-                    }
-                } else if capturing {
-                    writer.write_event(Event::Empty(e.to_owned())).ok();
-                }
-            }
-            Ok(Event::Text(e)) => {
-                if capturing {
-                    writer.write_event(Event::Text(e)).ok();
-                }
-            }
-            Ok(Event::CData(e)) => {
-                if capturing {
-                    writer.write_event(Event::CData(e)).ok();
-                }
-            }
-            Ok(Event::Comment(e)) => {
-                if capturing {
-                    writer.write_event(Event::Comment(e)).ok();
-                }
-            }
-            Ok(Event::End(e)) => {
-                if capturing {
-                    writer.write_event(Event::End(e.to_owned())).ok();
-                    depth -= 1;
-                    if depth == 0 {
-                        break;
-                    }
-                } else if e.name() == QName(b"game") {
-                    // shouldn't happen when not capturing as we increase idx on Start/Empty
-                }
-            }
-            Ok(Event::Eof) => break,
-            Err(_) => break,
-            _ => {}
-        }
+    fn parse(xml: &str) -> Vec<GameEntry> {
+        let source = DatSource {
+            path: Path::new("Test - Platform.xml"),
+            member: None,
+        };
+        parse_games(xml.as_bytes(), &source).unwrap().1
     }
 
-    let s = String::from_utf8_lossy(&output).to_string();
-    Ok(s)
+    #[test]
+    fn skipped_entries_do_not_leak_into_the_next() {
+        let games = parse(
+            r#"<datafile>
+                <machine name="dev" isdevice="yes" region="Japan" languages="Ja"/>
+                <machine name="dev2" runnable="no" region="Japan" languages="Ja"></machine>
+                <game region="Europe" languages="De"></game>
+                <game name="plain"><description>Plain</description></game>
+            </datafile>"#,
+        );
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].name, "plain");
+        assert_eq!(games[0].region, None);
+        assert_eq!(games[0].languages, None);
+    }
+    #[test]
+    fn resolves_clone_of_id() {
+        let games = parse(
+            r#"<datafile>
+                <game name="Foo (Japan)" id="0002" cloneofid="0001"><rom name="a.bin"/></game>
+                <game name="Foo (USA)" id="0001"><rom name="b.bin"/></game>
+                <game name="Bar (Europe)" id="0003" cloneofid="9999"></game>
+            </datafile>"#,
+        );
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].clone_of.as_deref(), Some("Foo (USA)"));
+        assert_eq!(games[1].clone_of, None);
+        assert_eq!(games[2].clone_of, None);
+    }
 }